@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use snek::board::Board;
+use snek::controller::BotKind;
+use snek::rules::GameRules;
+use snek::sim::{self, SimConfig};
+use snek::snek::{SnekObject, SnekPosition};
+
+const SIZE: usize = 64;
+
+/* Positions of every cell but the last few, in a fixed shuffled order. */
+fn nearly_full(free: usize) -> Vec<SnekPosition> {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut cells: Vec<SnekPosition> = (0..SIZE * SIZE)
+        .map(|i| SnekPosition { x: (i % SIZE) as i32, y: (i / SIZE) as i32 })
+        .collect();
+    for i in (1..cells.len()).rev() {
+        cells.swap(i, rng.gen_range(0..=i));
+    }
+    cells.truncate(SIZE * SIZE - free);
+    cells
+}
+
+/* How spawning worked with the old HashMap board: keep picking random
+ * cells until one turns out to be empty.
+ */
+fn hashmap_spawn(board: &HashMap<SnekPosition, SnekObject>, rng: &mut ChaCha8Rng) -> SnekPosition {
+    loop {
+        let pos = SnekPosition {
+            x: rng.gen_range(0..SIZE as u32) as i32,
+            y: rng.gen_range(0..SIZE as u32) as i32,
+        };
+        if !board.contains_key(&pos) {
+            return pos;
+        }
+    }
+}
+
+fn bench_spawn(c: &mut Criterion) {
+    let occupied = nearly_full(4);
+    let mut board = Board::new(SIZE, SIZE);
+    let mut map = HashMap::new();
+    for pos in &occupied {
+        board.set(pos, SnekObject::Rock);
+        map.insert(*pos, SnekObject::Rock);
+    }
+
+    let mut group = c.benchmark_group("spawn_nearly_full");
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    group.bench_function("dense", |b| b.iter(|| board.random_free(&mut rng)));
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    group.bench_function("hashmap", |b| b.iter(|| hashmap_spawn(&map, &mut rng)));
+    group.finish();
+
+    let mut group = c.benchmark_group("lookup");
+    let probe = &occupied[..256];
+    group.bench_function("dense", |b| {
+        b.iter(|| probe.iter().filter(|pos| board.get(pos) == Some(SnekObject::Rock)).count())
+    });
+    group.bench_function("hashmap", |b| {
+        b.iter(|| probe.iter().filter(|pos| map.get(pos) == Some(&SnekObject::Rock)).count())
+    });
+    group.finish();
+}
+
+/* Whole games, to see how the board holds up as Snek gets long. */
+fn bench_games(c: &mut Criterion) {
+    let config = SimConfig {
+        rules: GameRules::new()
+            .board_size(32, 24)
+            .rocks_per_berry(0)
+            .initial_rocks(0),
+        bot: BotKind::Cycle,
+        games: 1,
+        max_ticks: 5_000,
+        ..SimConfig::default()
+    };
+    c.bench_function("cycle_bot_5000_ticks", |b| b.iter(|| sim::play_game(black_box(&config), 7)));
+}
+
+criterion_group!(benches, bench_spawn, bench_games);
+criterion_main!(benches);
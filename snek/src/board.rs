@@ -0,0 +1,213 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::snek::{SnekObject, SnekPosition};
+
+/* Marks a cell that isn't in the free list. */
+const NOT_FREE: usize = usize::MAX;
+
+/* What's in every cell of the board, stored row by row, plus an index
+ * of the empty cells.  Looking up a cell, changing it and picking a
+ * random empty one all take the same time however full the board is.
+ */
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "BoardFile")]
+pub struct Board {
+    xsize: usize,
+    ysize: usize,
+    cells: Vec<SnekObject>,
+    /* Indices of the empty cells, in no particular order, and for each
+     * cell where it sits in that list.
+     */
+    free: Vec<usize>,
+    free_slot: Vec<usize>,
+}
+
+/* A Board as saved, checked before it's used so that a damaged save
+ * can't leave the free cell index pointing off the board.
+ */
+#[derive(Deserialize)]
+struct BoardFile {
+    xsize: usize,
+    ysize: usize,
+    cells: Vec<SnekObject>,
+    free: Vec<usize>,
+    free_slot: Vec<usize>,
+}
+
+impl TryFrom<BoardFile> for Board {
+    type Error = String;
+
+    fn try_from(file: BoardFile) -> Result<Board, String> {
+        if file.xsize.checked_mul(file.ysize) != Some(file.cells.len()) {
+            return Err(format!("{}x{} board has {} cells", file.xsize, file.ysize, file.cells.len()));
+        }
+        let empty = file.cells.iter().filter(|obj| **obj == SnekObject::Empty).count();
+        let indexed = file.free_slot.len() == file.cells.len()
+            && file.free.len() == empty
+            && file.free.iter().enumerate().all(|(slot, i)| {
+                file.cells.get(*i) == Some(&SnekObject::Empty) && file.free_slot[*i] == slot
+            });
+        if !indexed {
+            return Err("free cell index doesn't match the board".to_string());
+        }
+        Ok(Board {
+            xsize: file.xsize,
+            ysize: file.ysize,
+            cells: file.cells,
+            free: file.free,
+            free_slot: file.free_slot,
+        })
+    }
+}
+
+impl Board {
+    pub fn new(xsize: usize, ysize: usize) -> Board {
+        Board {
+            xsize,
+            ysize,
+            cells: vec![SnekObject::Empty; xsize * ysize],
+            free: (0..xsize * ysize).collect(),
+            free_slot: (0..xsize * ysize).collect(),
+        }
+    }
+
+    pub fn xsize(&self) -> usize {
+        self.xsize
+    }
+
+    pub fn ysize(&self) -> usize {
+        self.ysize
+    }
+
+    fn index(&self, pos: &SnekPosition) -> Option<usize> {
+        if pos.x < 0 || pos.y < 0 || pos.x as usize >= self.xsize || pos.y as usize >= self.ysize {
+            return None;
+        }
+        Some(pos.y as usize * self.xsize + pos.x as usize)
+    }
+
+    fn position(&self, index: usize) -> SnekPosition {
+        SnekPosition { x: (index % self.xsize) as i32, y: (index / self.xsize) as i32 }
+    }
+
+    /* What's at pos, or None if it's off the board. */
+    pub fn get(&self, pos: &SnekPosition) -> Option<SnekObject> {
+        self.index(pos).map(|i| self.cells[i])
+    }
+
+    /* Put obj at pos, replacing whatever was there.  Positions off the
+     * board are ignored.
+     */
+    pub fn set(&mut self, pos: &SnekPosition, obj: SnekObject) {
+        if let Some(i) = self.index(pos) {
+            self.set_index(i, obj);
+        }
+    }
+
+    pub fn clear(&mut self, pos: &SnekPosition) {
+        self.set(pos, SnekObject::Empty);
+    }
+
+    fn set_index(&mut self, i: usize, obj: SnekObject) {
+        let was_free = self.cells[i] == SnekObject::Empty;
+        let is_free = obj == SnekObject::Empty;
+        self.cells[i] = obj;
+        if was_free && !is_free {
+            let slot = self.free_slot[i];
+            self.free.swap_remove(slot);
+            if let Some(moved) = self.free.get(slot) {
+                self.free_slot[*moved] = slot;
+            }
+            self.free_slot[i] = NOT_FREE;
+        } else if !was_free && is_free {
+            self.free_slot[i] = self.free.len();
+            self.free.push(i);
+        }
+    }
+
+    /* Empty every cell holding obj.  Returns how many there were. */
+    pub fn clear_all(&mut self, obj: SnekObject) -> usize {
+        let mut count = 0;
+        for i in 0..self.cells.len() {
+            if self.cells[i] == obj {
+                self.set_index(i, SnekObject::Empty);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    pub fn free_cells(&self) -> usize {
+        self.free.len()
+    }
+
+    /* An empty cell picked at random, or None if the board is full.
+     * The pick is drawn as a u32 so seeded games come out the same on
+     * 32 and 64-bit targets.
+     */
+    pub fn random_free<R: Rng>(&self, rng: &mut R) -> Option<SnekPosition> {
+        if self.free.is_empty() {
+            return None;
+        }
+        let len: u32 = self.free.len().try_into().unwrap();
+        let i = self.free[rng.gen_range(0..len) as usize];
+        Some(self.position(i))
+    }
+
+    /* Every occupied cell, in row order. */
+    pub fn iter(&self) -> impl Iterator<Item = (SnekPosition, SnekObject)> + '_ {
+        self.cells.iter()
+            .enumerate()
+            .filter(|(_, obj)| **obj != SnekObject::Empty)
+            .map(move |(i, obj)| (self.position(i), *obj))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_free_cells() {
+        let mut board = Board::new(3, 2);
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        assert_eq!(board.free_cells(), 6);
+        for _ in 0..5 {
+            let pos = board.random_free(&mut rng).unwrap();
+            board.set(&pos, SnekObject::Rock);
+        }
+        assert_eq!(board.free_cells(), 1);
+        let last = board.random_free(&mut rng).unwrap();
+        assert_eq!(board.get(&last), Some(SnekObject::Empty));
+        board.set(&last, SnekObject::Wall);
+        assert_eq!(board.random_free(&mut rng), None);
+
+        assert_eq!(board.clear_all(SnekObject::Rock), 5);
+        assert_eq!(board.free_cells(), 5);
+        assert_eq!(board.iter().collect::<Vec<_>>(), vec![(last, SnekObject::Wall)]);
+        assert_eq!(board.get(&SnekPosition { x: 3, y: 0 }), None);
+    }
+
+    #[test]
+    fn test_load_checks_free_cells() {
+        let mut board = Board::new(3, 2);
+        board.set(&SnekPosition { x: 1, y: 1 }, SnekObject::Rock);
+        let saved = serde_json::to_value(&board).unwrap();
+        let loaded: Board = serde_json::from_value(saved.clone()).unwrap();
+        assert_eq!(loaded.free_cells(), 5);
+        assert_eq!(loaded.get(&SnekPosition { x: 1, y: 1 }), Some(SnekObject::Rock));
+
+        let mut bad = saved.clone();
+        bad["free"][0] = 99.into();
+        assert!(serde_json::from_value::<Board>(bad).is_err());
+        let mut bad = saved.clone();
+        bad["free"][0] = 4.into();
+        assert!(serde_json::from_value::<Board>(bad).is_err());
+        let mut bad = saved;
+        bad["xsize"] = 4.into();
+        assert!(serde_json::from_value::<Board>(bad).is_err());
+    }
+}
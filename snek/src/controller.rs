@@ -0,0 +1,443 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::str::FromStr;
+
+use crate::snek::{BerryKind, SnekDirection, SnekGame, SnekId, SnekObject, SnekPosition, MAX_PENDING_TURNS};
+
+/* Something that steers a Snek: a person at the keyboard, a bot, or a
+ * script.  Once per tick the controller gets a read-only look at the
+ * game and says which way its Snek should go next.  Answering with
+ * the direction the Snek is already going keeps it going straight.
+ */
+pub trait SnekController {
+    fn next_dir(&mut self, game: &SnekGame, id: SnekId) -> SnekDirection;
+}
+
+/* Ask each controller where its Snek wants to go, controllers[i]
+ * steering Snek i, and return the turns to hand to set_snek_dir() (or
+ * a ReplayRecorder) before the next step().  Dead Sneks aren't asked,
+ * and carrying straight on isn't a turn.
+ */
+pub fn collect_turns(game: &SnekGame, controllers: &mut [&mut dyn SnekController]) -> Vec<(SnekId, SnekDirection)> {
+    let mut turns = Vec::new();
+    for (id, controller) in controllers.iter_mut().enumerate() {
+        let snek = match game.snek(id) {
+            Some(snek) if snek.is_alive() => snek,
+            _ => continue,
+        };
+        let dir = controller.next_dir(game, id);
+        if dir != snek.next_dir() {
+            turns.push((id, dir));
+        }
+    }
+    turns
+}
+
+/* Steering from key presses.  Frontends push() turns as the keys come
+ * in and the controller plays them back one per tick, dropping any
+ * that would have Snek double back on itself.
+ */
+#[derive(Debug, Default)]
+pub struct HumanController {
+    turns: VecDeque<SnekDirection>,
+}
+
+impl HumanController {
+    pub fn new() -> HumanController {
+        HumanController::default()
+    }
+
+    pub fn push(&mut self, dir: SnekDirection) {
+        if self.turns.len() < MAX_PENDING_TURNS {
+            self.turns.push_back(dir);
+        }
+    }
+}
+
+impl SnekController for HumanController {
+    fn next_dir(&mut self, game: &SnekGame, id: SnekId) -> SnekDirection {
+        let curdir = game.sneks()[id].head_dir();
+        while let Some(dir) = self.turns.pop_front() {
+            if dir != curdir && dir != curdir.opposite() {
+                return dir;
+            }
+        }
+        curdir
+    }
+}
+
+/* Turns fixed in advance, keyed by the tick they should be made on.
+ * Handy for tests and demos.
+ */
+#[derive(Debug, Default)]
+pub struct ScriptedController {
+    turns: HashMap<u64, SnekDirection>,
+}
+
+impl ScriptedController {
+    pub fn new<I: IntoIterator<Item = (u64, SnekDirection)>>(turns: I) -> ScriptedController {
+        ScriptedController { turns: turns.into_iter().collect() }
+    }
+}
+
+impl SnekController for ScriptedController {
+    fn next_dir(&mut self, game: &SnekGame, id: SnekId) -> SnekDirection {
+        match self.turns.get(&game.ticks()) {
+            Some(dir) => *dir,
+            None => game.sneks()[id].head_dir(),
+        }
+    }
+}
+
+const DIRECTIONS: [SnekDirection; 4] = [
+    SnekDirection::North,
+    SnekDirection::East,
+    SnekDirection::South,
+    SnekDirection::West,
+];
+
+/* Cells a Snek can move into without dying, ignoring other heads
+ * that might get there at the same time.
+ */
+fn is_open(game: &SnekGame, pos: &SnekPosition) -> bool {
+    matches!(game.get_cell(pos), SnekObject::Empty | SnekObject::Berry(_))
+}
+
+/* A bot that takes the shortest path to the nearest berry worth
+ * eating, steering round walls, rocks and Sneks.  When every berry is
+ * cut off it heads for whichever open neighbouring cell leads to the
+ * most room, to stay alive until a path opens up.
+ */
+#[derive(Debug, Default)]
+pub struct PathfindingBot;
+
+impl PathfindingBot {
+    pub fn new() -> PathfindingBot {
+        PathfindingBot
+    }
+
+    /* First step of the shortest path from the head to a berry. */
+    fn path_to_berry(&self, game: &SnekGame, id: SnekId) -> Option<SnekDirection> {
+        let snek = &game.sneks()[id];
+        let start = snek.head_pos();
+
+        /* Breadth-first search, remembering for every cell reached the
+         * move out of the head that leads there.
+         */
+        let mut first_step = HashMap::new();
+        let mut queue = VecDeque::new();
+        for dir in DIRECTIONS.iter().filter(|d| **d != snek.head_dir().opposite()) {
+            let pos = game.neighbor(&start, *dir);
+            if is_open(game, &pos) && !first_step.contains_key(&pos) {
+                first_step.insert(pos, *dir);
+                queue.push_back(pos);
+            }
+        }
+        while let Some(pos) = queue.pop_front() {
+            let dir = first_step[&pos];
+            if let SnekObject::Berry(BerryKind::Normal | BerryKind::Golden) = game.get_cell(&pos) {
+                return Some(dir);
+            }
+            for d in DIRECTIONS {
+                let next = game.neighbor(&pos, d);
+                if is_open(game, &next) && !first_step.contains_key(&next) {
+                    first_step.insert(next, dir);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /* How many open cells can be reached from pos. */
+    fn room(&self, game: &SnekGame, pos: SnekPosition) -> usize {
+        let mut seen = HashSet::new();
+        let mut stack = vec![pos];
+        while let Some(pos) = stack.pop() {
+            if !is_open(game, &pos) || !seen.insert(pos) {
+                continue;
+            }
+            for d in DIRECTIONS {
+                stack.push(game.neighbor(&pos, d));
+            }
+        }
+        seen.len()
+    }
+}
+
+impl SnekController for PathfindingBot {
+    fn next_dir(&mut self, game: &SnekGame, id: SnekId) -> SnekDirection {
+        if let Some(dir) = self.path_to_berry(game, id) {
+            return dir;
+        }
+        let snek = &game.sneks()[id];
+        let head = snek.head_pos();
+        DIRECTIONS.iter()
+            .filter(|d| **d != snek.head_dir().opposite())
+            .map(|d| (*d, game.neighbor(&head, *d)))
+            .filter(|(_, pos)| is_open(game, pos))
+            .max_by_key(|(_, pos)| self.room(game, *pos))
+            .map(|(d, _)| d)
+            .unwrap_or(snek.head_dir())
+    }
+}
+
+/* How far short of its own tail a shortcut has to land, on top of
+ * any growth still to come.
+ */
+const SHORTCUT_MARGIN: usize = 3;
+
+/* A bot that can't trap itself on an open board.  It follows a
+ * Hamiltonian cycle, a loop through every cell exactly once, so its
+ * tail is always somewhere ahead of it.  While the Snek is short it
+ * cuts across the cycle towards the berry, as long as the jump leaves
+ * the head behind its tail and doesn't skip the berry.  When rocks or
+ * walls sit on the cycle between the head and the berry it hands over
+ * to the pathfinding bot to get round them, which is where the
+ * guarantee runs out.  Boards with an odd number of cells have no such
+ * cycle, so the pathfinding bot plays those on its own.
+ */
+#[derive(Debug, Default)]
+pub struct HamiltonianBot {
+    /* Board size the cycle was built for, each cell's place along it
+     * indexed by y * xsize + x, and the cells in cycle order.
+     */
+    size: (usize, usize),
+    order: Vec<usize>,
+    cycle: Vec<SnekPosition>,
+}
+
+impl HamiltonianBot {
+    pub fn new() -> HamiltonianBot {
+        HamiltonianBot::default()
+    }
+
+    /* Lay the cycle out over a board with an even number of rows,
+     * transposing if it's only the columns that are even: along the
+     * top row, back and forth through the other rows leaving out the
+     * first column, then up the first column to the start.
+     */
+    fn build_cycle(xsize: usize, ysize: usize) -> Vec<usize> {
+        if xsize < 2 || ysize < 2 || !(xsize * ysize).is_multiple_of(2) {
+            return Vec::new();
+        }
+        let transpose = !ysize.is_multiple_of(2);
+        let (w, h) = if transpose { (ysize, xsize) } else { (xsize, ysize) };
+        let mut path = Vec::with_capacity(w * h);
+        for x in 0..w {
+            path.push((x, 0));
+        }
+        for y in 1..h {
+            if y % 2 == 1 {
+                path.extend((1..w).rev().map(|x| (x, y)));
+            } else {
+                path.extend((1..w).map(|x| (x, y)));
+            }
+        }
+        path.extend((1..h).rev().map(|y| (0, y)));
+
+        let mut order = vec![0; xsize * ysize];
+        for (i, (x, y)) in path.into_iter().enumerate() {
+            let (x, y) = if transpose { (y, x) } else { (x, y) };
+            order[y * xsize + x] = i;
+        }
+        order
+    }
+}
+
+impl SnekController for HamiltonianBot {
+    fn next_dir(&mut self, game: &SnekGame, id: SnekId) -> SnekDirection {
+        let (xsize, ysize) = (game.xsize(), game.ysize());
+        if self.size != (xsize, ysize) {
+            self.size = (xsize, ysize);
+            self.order = HamiltonianBot::build_cycle(xsize, ysize);
+            self.cycle = vec![SnekPosition { x: 0, y: 0 }; self.order.len()];
+            for (i, place) in self.order.iter().enumerate() {
+                self.cycle[*place] = SnekPosition { x: (i % xsize) as i32, y: (i / xsize) as i32 };
+            }
+        }
+        if self.order.is_empty() {
+            return PathfindingBot::new().next_dir(game, id);
+        }
+
+        let n = self.order.len();
+        let order = &self.order;
+        let place = |pos: &SnekPosition| order[pos.y as usize * xsize + pos.x as usize];
+        let dist = |from: usize, to: usize| (to + n - from) % n;
+
+        let snek = &game.sneks()[id];
+        let head = place(&snek.head_pos());
+        let to_tail = match snek.segments().last() {
+            Some(tail) => dist(head, place(tail)),
+            None => n,
+        };
+        let to_berry = game.cells()
+            .filter(|(_, obj)| matches!(obj, SnekObject::Berry(BerryKind::Normal | BerryKind::Golden)))
+            .map(|(pos, _)| dist(head, place(&pos)))
+            .min()
+            .unwrap_or(n);
+        let blocked = to_berry < n && (1..to_berry).any(|i| {
+            matches!(game.get_cell(&self.cycle[(head + i) % n]), SnekObject::Rock | SnekObject::Wall)
+        });
+        if blocked {
+            return PathfindingBot::new().next_dir(game, id);
+        }
+
+        /* Once the Snek fills half the board there's no slack left to
+         * cut corners with.
+         */
+        let max_jump = if (snek.length() + snek.pending_growth()) * 2 > n {
+            1
+        } else {
+            to_tail.saturating_sub(snek.pending_growth() + SHORTCUT_MARGIN).min(to_berry).max(1)
+        };
+
+        let head_pos = snek.head_pos();
+        DIRECTIONS.iter()
+            .filter(|d| **d != snek.head_dir().opposite())
+            .map(|d| (*d, game.neighbor(&head_pos, *d)))
+            .filter(|(_, pos)| is_open(game, pos))
+            .map(|(d, pos)| (d, dist(head, place(&pos))))
+            .filter(|(_, jump)| (1..=max_jump).contains(jump))
+            .max_by_key(|(_, jump)| *jump)
+            .map(|(d, _)| d)
+            .unwrap_or_else(|| PathfindingBot::new().next_dir(game, id))
+    }
+}
+
+/* The built-in bots, by the names frontends and tools know them by. */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BotKind {
+    /* PathfindingBot */
+    Path,
+    /* HamiltonianBot */
+    Cycle,
+}
+
+impl BotKind {
+    pub fn controller(&self) -> Box<dyn SnekController> {
+        match self {
+            BotKind::Path => Box::new(PathfindingBot::new()),
+            BotKind::Cycle => Box::new(HamiltonianBot::new()),
+        }
+    }
+}
+
+impl FromStr for BotKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BotKind, String> {
+        match s {
+            "path" => Ok(BotKind::Path),
+            "cycle" => Ok(BotKind::Cycle),
+            _ => Err(format!("unknown bot '{}' (expected path or cycle)", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::SnekMap;
+    use crate::rules::GameRules;
+    use crate::snek::GameState;
+
+    fn play(game: &mut SnekGame, controller: &mut dyn SnekController, ticks: usize) {
+        for _ in 0..ticks {
+            for (id, dir) in collect_turns(game, &mut [&mut *controller]) {
+                game.set_snek_dir(id, dir);
+            }
+            game.step();
+        }
+    }
+
+    #[test]
+    fn test_pathfinding_bot() {
+        let rules = GameRules::new()
+            .board_size(16, 12)
+            .start(SnekPosition { x: 3, y: 3 }, SnekDirection::East)
+            .rocks_per_berry(0);
+        let mut game = SnekGame::with_rules(rules, 3);
+        let mut bot = PathfindingBot::new();
+        play(&mut game, &mut bot, 300);
+        assert_eq!(game.state(), GameState::Running);
+        assert!(game.points() >= 100);
+    }
+
+    #[test]
+    fn test_bot_survives_without_berries() {
+        let rules = GameRules::new()
+            .board_size(8, 8)
+            .start(SnekPosition { x: 3, y: 3 }, SnekDirection::East)
+            .starting_length(6)
+            .initial_berries(0)
+            .initial_rocks(0);
+        let mut game = SnekGame::with_rules(rules, 0);
+        let mut bot = PathfindingBot::new();
+        play(&mut game, &mut bot, 50);
+        assert_eq!(game.state(), GameState::Running);
+    }
+
+    #[test]
+    fn test_human_and_scripted_controllers() {
+        let mut human = HumanController::new();
+        let mut game = SnekGame::with_seed(32, 24, 1);
+        human.push(SnekDirection::West);
+        human.push(SnekDirection::South);
+        play(&mut game, &mut human, 1);
+        assert_eq!(game.snek_head_dir(), SnekDirection::South);
+
+        let mut script = ScriptedController::new([(2, SnekDirection::North), (4, SnekDirection::West)]);
+        let mut game = SnekGame::with_seed(32, 24, 1);
+        play(&mut game, &mut script, 6);
+        assert_eq!(game.snek_head_pos(), SnekPosition { x: 10, y: 8 });
+    }
+
+    #[test]
+    fn test_hamiltonian_cycle() {
+        for (xsize, ysize) in [(4, 4), (5, 4), (4, 5), (10, 8)] {
+            let order = HamiltonianBot::build_cycle(xsize, ysize);
+            let mut cells = vec![(0, 0); order.len()];
+            for (i, place) in order.iter().enumerate() {
+                cells[*place] = ((i % xsize) as i32, (i / xsize) as i32);
+            }
+            for (i, a) in cells.iter().enumerate() {
+                let b = cells[(i + 1) % cells.len()];
+                assert_eq!((a.0 - b.0).abs() + (a.1 - b.1).abs(), 1);
+            }
+        }
+        assert!(HamiltonianBot::build_cycle(5, 5).is_empty());
+    }
+
+    /* The rock sits on the cycle between Snek and the berry.  Following
+     * the cycle would only ever lead back to the rock, so the bot has to
+     * steer round it.
+     */
+    #[test]
+    fn test_hamiltonian_bot_blocked() {
+        let map: SnekMap = "→_█__▄\n______\n______\n______\n".parse().unwrap();
+        let mut game = SnekGame::on_map(&map, GameRules::new(), 2);
+        play(&mut game, &mut HamiltonianBot::new(), 12);
+        assert!(!game.is_over());
+        assert!(game.points() > 0);
+    }
+
+    /* Plays until the board is full. */
+    #[test]
+    fn test_hamiltonian_bot_soak() {
+        let rules = GameRules::new()
+            .board_size(10, 8)
+            .start(SnekPosition { x: 2, y: 3 }, SnekDirection::East)
+            .rocks_per_berry(0)
+            .initial_rocks(0)
+            .bonus_berry_chance(0);
+        let mut game = SnekGame::with_rules(rules, 5);
+        let mut bot = HamiltonianBot::new();
+        let mut ticks = 0;
+        while !game.is_over() && ticks < 20_000 {
+            play(&mut game, &mut bot, 1);
+            ticks += 1;
+        }
+        assert_eq!(game.state(), GameState::Won);
+        assert_eq!(game.free_cells(), 0);
+    }
+}
@@ -0,0 +1,352 @@
+use serde::Serialize;
+
+use crate::clock::ManualClock;
+use crate::map::SnekMap;
+use crate::rules::GameRules;
+use crate::snek::{GameState, SnekDirection, SnekEvent, SnekGame, SnekObject, SnekPosition};
+
+/* A Gym-style wrapper round SnekGame for training agents:
+ *
+ *     let mut env = SnekEnv::new(EnvConfig::default());
+ *     let mut obs = env.reset(seed);
+ *     loop {
+ *         let step = env.step(agent.act(&obs));
+ *         obs = step.observation;
+ *         if step.done { break; }
+ *     }
+ *
+ * The agent steers the first Snek.  Games run on a ManualClock, so an
+ * episode is entirely decided by its seed and actions.
+ */
+
+/* Observation channels, in the order they're laid out in a grid.  The
+ * wall channel holds the walls on the board.  On a board that doesn't
+ * wrap, the edge itself is deadly too, but the whole-board grid leaves
+ * it out on purpose: the grid stops where the board does, so its own
+ * border is the edge, and marking the outermost cells would tell the
+ * agent they can't be entered when they can.  The egocentric view does
+ * reach past the edge, and marks those cells as wall.
+ */
+pub const CHANNEL_HEAD: usize = 0;
+pub const CHANNEL_BODY: usize = 1;
+pub const CHANNEL_BERRY: usize = 2;
+pub const CHANNEL_ROCK: usize = 3;
+pub const CHANNEL_WALL: usize = 4;
+pub const CHANNELS: usize = 5;
+
+/* What the agent can do on a tick.  Turn picks a compass direction;
+ * Left, Right and Straight are relative to the way the Snek is facing,
+ * which suits agents working from the egocentric view.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Turn(SnekDirection),
+    Left,
+    Right,
+    Straight,
+}
+
+impl Action {
+    fn direction(&self, facing: SnekDirection) -> SnekDirection {
+        match (self, facing) {
+            (Action::Turn(dir), _) => *dir,
+            (Action::Straight, dir) => dir,
+            (Action::Left, SnekDirection::North) | (Action::Right, SnekDirection::South) => SnekDirection::West,
+            (Action::Left, SnekDirection::East) | (Action::Right, SnekDirection::West) => SnekDirection::North,
+            (Action::Left, SnekDirection::South) | (Action::Right, SnekDirection::North) => SnekDirection::East,
+            (Action::Left, SnekDirection::West) | (Action::Right, SnekDirection::East) => SnekDirection::South,
+        }
+    }
+}
+
+/* Rewards handed out each step, summed.  Only points and death count
+ * by default; the rest are there to shape learning.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct RewardConfig {
+    /* Per point scored, so a normal berry is worth points_per_berry
+     * times this.
+     */
+    pub per_point: f32,
+    pub death: f32,
+    pub win: f32,
+    /* Every tick survived.  Negative values hurry the agent along. */
+    pub per_tick: f32,
+    /* For moving closer to the nearest berry, and taken away again
+     * for moving further off.
+     */
+    pub approach: f32,
+}
+
+impl Default for RewardConfig {
+    fn default() -> RewardConfig {
+        RewardConfig {
+            per_point: 0.1,
+            death: -1.0,
+            win: 10.0,
+            per_tick: 0.0,
+            approach: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EnvConfig {
+    pub rules: GameRules,
+    pub map: Option<SnekMap>,
+    pub reward: RewardConfig,
+    /* Also observe a square of (2 * radius + 1) cells centred on the
+     * head and turned so the Snek is facing up.
+     */
+    pub view_radius: Option<usize>,
+    /* End episodes that run this long, flagged as truncated. */
+    pub max_ticks: Option<u64>,
+}
+
+/* A stack of CHANNELS planes of width * height cells, stored channel
+ * by channel, then row by row.  Cells are 1.0 where the channel's
+ * object is and 0.0 elsewhere.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f32>,
+}
+
+impl Grid {
+    fn new(width: usize, height: usize) -> Grid {
+        Grid { width, height, data: vec![0.0; CHANNELS * width * height] }
+    }
+
+    pub fn get(&self, channel: usize, x: usize, y: usize) -> f32 {
+        self.data[(channel * self.height + y) * self.width + x]
+    }
+
+    fn set(&mut self, channel: usize, x: usize, y: usize) {
+        self.data[(channel * self.height + y) * self.width + x] = 1.0;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Observation {
+    /* The whole board. */
+    pub board: Grid,
+    /* The egocentric view, if the config asks for one. */
+    pub view: Option<Grid>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepInfo {
+    pub events: Vec<SnekEvent>,
+    pub points: u64,
+    pub length: usize,
+    pub ticks: u64,
+    /* The episode hit max_ticks rather than ending by itself. */
+    pub truncated: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f32,
+    pub done: bool,
+    pub info: StepInfo,
+}
+
+#[derive(Debug)]
+pub struct SnekEnv {
+    config: EnvConfig,
+    game: SnekGame,
+}
+
+impl SnekEnv {
+    pub fn new(config: EnvConfig) -> SnekEnv {
+        let game = SnekEnv::new_game(&config, 0);
+        SnekEnv { config, game }
+    }
+
+    fn new_game(config: &EnvConfig, seed: u64) -> SnekGame {
+        let mut game = match &config.map {
+            Some(map) => SnekGame::on_map(map, config.rules.clone(), seed),
+            None => SnekGame::with_rules(config.rules.clone(), seed),
+        };
+        game.set_clock(Box::new(ManualClock::new()));
+        game
+    }
+
+    /* The game being played, for rendering or poking at directly. */
+    pub fn game(&self) -> &SnekGame {
+        &self.game
+    }
+
+    /* Start a new episode. */
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.game = SnekEnv::new_game(&self.config, seed);
+        self.observe()
+    }
+
+    pub fn step(&mut self, action: Action) -> Step {
+        let reward = &self.config.reward;
+        let points = self.game.points();
+        let distance = self.berry_distance();
+
+        let dir = action.direction(self.game.snek_head_dir());
+        self.game.set_snekdir(dir);
+        let events = self.game.step();
+
+        let died = !self.game.sneks()[0].is_alive();
+        let mut total = (self.game.points() - points) as f32 * reward.per_point;
+        match self.game.state() {
+            GameState::Won => total += reward.win,
+            _ if died => total += reward.death,
+            _ => total += reward.per_tick,
+        }
+        if !died {
+            match (distance, self.berry_distance()) {
+                (Some(before), Some(after)) if after < before => total += reward.approach,
+                (Some(before), Some(after)) if after > before => total -= reward.approach,
+                _ => {},
+            }
+        }
+
+        let truncated = !died && !self.game.is_over()
+            && self.config.max_ticks.is_some_and(|max| self.game.ticks() >= max);
+        Step {
+            observation: self.observe(),
+            reward: total,
+            done: died || self.game.is_over() || truncated,
+            info: StepInfo {
+                events,
+                points: self.game.points(),
+                length: self.game.length(),
+                ticks: self.game.ticks(),
+                truncated,
+            },
+        }
+    }
+
+    /* Steps to the nearest berry as the crow flies, ignoring anything
+     * in the way.
+     */
+    fn berry_distance(&self) -> Option<i32> {
+        let head = self.game.snek_head_pos();
+        let (w, h) = (self.game.xsize() as i32, self.game.ysize() as i32);
+        let wrap = self.game.rules().wrap;
+        self.game.cells()
+            .filter(|(_, obj)| matches!(obj, SnekObject::Berry(_)))
+            .map(|(pos, _)| {
+                let (dx, dy) = ((pos.x - head.x).abs(), (pos.y - head.y).abs());
+                if wrap { dx.min(w - dx) + dy.min(h - dy) } else { dx + dy }
+            })
+            .min()
+    }
+
+    fn channel(&self, obj: SnekObject) -> Option<usize> {
+        match obj {
+            SnekObject::Head(0) => Some(CHANNEL_HEAD),
+            SnekObject::Head(_) | SnekObject::Segment(_) => Some(CHANNEL_BODY),
+            SnekObject::Berry(_) => Some(CHANNEL_BERRY),
+            SnekObject::Rock => Some(CHANNEL_ROCK),
+            SnekObject::Wall => Some(CHANNEL_WALL),
+            SnekObject::Empty => None,
+        }
+    }
+
+    pub fn observe(&self) -> Observation {
+        let mut board = Grid::new(self.game.xsize(), self.game.ysize());
+        for (pos, obj) in self.game.cells() {
+            if let Some(channel) = self.channel(obj) {
+                board.set(channel, pos.x as usize, pos.y as usize);
+            }
+        }
+
+        let view = self.config.view_radius.map(|radius| {
+            let size = 2 * radius + 1;
+            let mut view = Grid::new(size, size);
+            let head = self.game.snek_head_pos();
+            let facing = self.game.snek_head_dir();
+            for vy in 0..size {
+                for vx in 0..size {
+                    let (dx, dy) = (vx as i32 - radius as i32, vy as i32 - radius as i32);
+                    /* Turn the view so straight ahead is up. */
+                    let (bx, by) = match facing {
+                        SnekDirection::North => (dx, dy),
+                        SnekDirection::East => (-dy, dx),
+                        SnekDirection::South => (-dx, -dy),
+                        SnekDirection::West => (dy, -dx),
+                    };
+                    let mut pos = SnekPosition { x: head.x + bx, y: head.y + by };
+                    if self.game.rules().wrap {
+                        pos.x = pos.x.rem_euclid(self.game.xsize() as i32);
+                        pos.y = pos.y.rem_euclid(self.game.ysize() as i32);
+                    }
+                    if let Some(channel) = self.channel(self.game.get_cell(&pos)) {
+                        view.set(channel, vx, vy);
+                    }
+                }
+            }
+            view
+        });
+
+        Observation { board, view }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> EnvConfig {
+        EnvConfig {
+            rules: GameRules::new()
+                .board_size(8, 6)
+                .initial_rocks(0)
+                .start(SnekPosition { x: 2, y: 1 }, SnekDirection::East),
+            view_radius: Some(2),
+            ..EnvConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_observation() {
+        let mut env = SnekEnv::new(config());
+        let obs = env.reset(1);
+        assert_eq!(obs.board.data.len(), CHANNELS * 8 * 6);
+        assert_eq!(obs.board.get(CHANNEL_HEAD, 2, 1), 1.0);
+        assert_eq!(obs.board.data.iter().filter(|v| **v == 1.0).count(), 2);
+        // The board's edge isn't in the wall channel; the grid's bounds are
+        // the edge.
+        assert!((0..8).all(|x| obs.board.get(CHANNEL_WALL, x, 0) == 0.0));
+        assert!((0..6).all(|y| obs.board.get(CHANNEL_WALL, 7, y) == 0.0));
+
+        // Facing east, the cell ahead of the head is at the top of the
+        // view and the cells off the north edge of the board are on its
+        // left.
+        let view = obs.view.unwrap();
+        assert_eq!(view.get(CHANNEL_HEAD, 2, 2), 1.0);
+        assert_eq!(view.get(CHANNEL_WALL, 0, 0), 1.0);
+        assert_eq!(view.get(CHANNEL_WALL, 4, 0), 0.0);
+
+        let step = env.step(Action::Left);
+        assert_eq!(env.game().snek_head_dir(), SnekDirection::North);
+        assert_eq!(step.observation.board.get(CHANNEL_HEAD, 2, 0), 1.0);
+        assert_eq!(step.observation.board.get(CHANNEL_BODY, 2, 1), 1.0);
+    }
+
+    #[test]
+    fn test_episode_ends() {
+        let mut env = SnekEnv::new(config());
+        env.reset(1);
+        assert!(!env.step(Action::Turn(SnekDirection::North)).done);
+        let step = env.step(Action::Straight);
+        assert!(step.done && !step.info.truncated);
+        assert_eq!(step.reward, -1.0);
+
+        let mut env = SnekEnv::new(EnvConfig { max_ticks: Some(2), ..config() });
+        env.reset(1);
+        assert!(!env.step(Action::Straight).done);
+        let step = env.step(Action::Straight);
+        assert!(step.done && step.info.truncated);
+    }
+}
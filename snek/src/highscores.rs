@@ -1,23 +1,57 @@
-use std::env;
-use rusqlite::{params, Connection, Result};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use rusqlite::{params, Connection, Result, Row};
 use chrono::{DateTime, Utc};
 
-#[derive(Debug)]
-struct HighScore {
-    name: String,
-    date: String,
-    points: u64,
-    seconds: u64,
+use crate::snek::SnekGame;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HighScore {
+    pub name: String,
+    pub date: String,
+    pub points: u64,
+    pub seconds: u64,
+}
+
+impl HighScore {
+    /* Build a score entry for a finished game, dated today. */
+    pub fn from_game(name: &str, game: &SnekGame) -> HighScore {
+        let now: DateTime<Utc> = Utc::now();
+        HighScore {
+            name: name.to_string(),
+            date: now.format("%Y-%m-%d").to_string(),
+            points: game.points(),
+            seconds: game.elapsed().as_secs(),
+        }
+    }
+
+    fn from_row(row: &Row) -> Result<HighScore> {
+        Ok(HighScore {
+            name: row.get(0)?,
+            date: row.get(1)?,
+            points: row.get(2)?,
+            seconds: row.get(3)?,
+        })
+    }
+}
+
+impl fmt::Display for HighScore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<4} {:>8} {:>6}s  {}", self.name, self.points, self.seconds, self.date)
+    }
 }
 
+/* Best first: most points, then the quickest time, then the newest. */
+const BEST_FIRST: &str = "ORDER BY points DESC, seconds ASC, date DESC, rowid DESC";
+
 #[derive(Debug)]
-struct HighScoreTable {
+pub struct HighScoreTable {
     conn: Connection,
 }
 
 impl HighScoreTable {
-    pub fn add(&self, score: HighScore) {
-        // println!("Adding {:?}", score);
+    pub fn add(&self, score: HighScore) -> Result<()> {
         self.conn.execute(
             "INSERT INTO highscores (
                 player_initials,
@@ -28,64 +62,57 @@ impl HighScoreTable {
                 ?1, ?2, ?3, ?4
             )",
             params![score.name, score.date, score.points, score.seconds ],
-        ).unwrap();
+        )?;
+        Ok(())
     }
 
-
+    /* The single best score, or None if the table is empty. */
     pub fn get_highscore(&self) -> Option<HighScore> {
-        let mut stmt =(self.conn.prepare(
+        self.get_top_scores(1).ok()?.pop()
+    }
+
+    /* Up to count scores, best first. */
+    pub fn get_top_scores(&self, count: usize) -> Result<Vec<HighScore>> {
+        let mut stmt = self.conn.prepare(&format!(
             "SELECT player_initials, date, points, seconds
             FROM highscores
-            WHERE points == (SELECT MAX(points) FROM highscores)"
-        )).unwrap();
-        // Desired behavior:
-        // Try to get highest score
-        // If multiple records are returned (same score), go for
-        // shortest time.  If that somehow matches, winner is the
-        // newest record.
-        let mut highscores: Vec<HighScore> = Vec::new();
-        let scores = stmt.query_map([], |row| {
-            Ok(HighScore {
-                name: row.get(0).unwrap(),
-                date: row.get(1).unwrap(),
-                points: row.get(2).unwrap(),
-                seconds: row.get(3).unwrap(),
-            })
-        });
-        
-        // for s in scores {
-        //     println!("{}", s);
-        // }
-
-        Some(scores.iter()[0])
-        // self.conn.execute(
-        //     "SELECT FROM highscores (
-        //         player_initials,
-        //         date,
-        //         points,
-        //         seconds
-        //     ) WHERE (
-        //         ?1, ?2, ?3, ?4
-        //     )"
-            
-        // ).unwrap();
-        // None
+            {}
+            LIMIT ?1", BEST_FIRST
+        ))?;
+        let scores = stmt.query_map(params![count as i64], HighScore::from_row)?;
+        scores.collect()
     }
 
-    // Option?
-    pub fn get_top_scores(&self) -> Option<Vec<HighScore>> {
-        None
+    /* Up to count of one player's scores, best first. */
+    pub fn get_player_scores(&self, name: &str, count: usize) -> Result<Vec<HighScore>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT player_initials, date, points, seconds
+            FROM highscores
+            WHERE player_initials = ?1
+            {}
+            LIMIT ?2", BEST_FIRST
+        ))?;
+        let scores = stmt.query_map(params![name, count as i64], HighScore::from_row)?;
+        scores.collect()
     }
 
+    /* Open the table in name.db in the current directory. */
     pub fn new(name: &str) -> HighScoreTable {
-        HighScoreTable {
-            conn: HighScoreTable::open_table(name),
+        HighScoreTable::open(format!("{}.db", name)).unwrap()
+    }
+
+    /* Open the table in the database at path, creating both if need be. */
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<HighScoreTable> {
+        if let Some(dir) = path.as_ref().parent() {
+            let _ = fs::create_dir_all(dir);
         }
+        Ok(HighScoreTable {
+            conn: HighScoreTable::open_table(path.as_ref())?,
+        })
     }
 
-    fn open_table(name: &str) -> Connection {
-        let db_name = format!("{}.db", name);
-        let conn = Connection::open(db_name).unwrap();
+    fn open_table(path: &Path) -> Result<Connection> {
+        let conn = Connection::open(path)?;
         conn.execute(
             "CREATE TABLE IF NOT EXISTS highscores (
                 player_initials TEXT,
@@ -94,17 +121,21 @@ impl HighScoreTable {
                 seconds INTEGER
             )",
             [],
-        ).unwrap();
-        conn
+        )?;
+        Ok(conn)
     }
 }
 
-// Impl display, have it get the top 10 records and display them.
+/* Where the frontends keep their scores unless told otherwise. */
+pub fn default_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("snek").join("highscores.db"))
+}
 
-#[cfg(test)] 
+#[cfg(test)]
 mod tests {
     use super::*;
     use rand::Rng;
+    use tempfile::TempDir;
 
     fn get_random_initials() -> String {
         let mut rng = rand::thread_rng();
@@ -133,12 +164,11 @@ mod tests {
             points: rng.gen_range(1..5000),
             seconds: rng.gen_range(1..3600),
         };
-        table.add(highscore);
+        table.add(highscore).unwrap();
     }
 
-    fn get_random_highscore_table(nentries: usize) -> HighScoreTable {
-        let mut table = HighScoreTable::new("testo");
-        // let mut dbfile = NamedTempFile::new().unwrap();
+    fn get_random_highscore_table(dir: &TempDir, nentries: usize) -> HighScoreTable {
+        let table = HighScoreTable::open(dir.path().join("testo.db")).unwrap();
         for _ in 0..nentries {
             add_random_highscore(&table);
         }
@@ -147,10 +177,22 @@ mod tests {
 
     #[test]
     fn test_highscore_table() {
-        let mut table = get_random_highscore_table(50);
+        let dir = tempfile::tempdir().unwrap();
+        let table = get_random_highscore_table(&dir, 50);
         println!("{:?}", table);
         println!("highscore: {:?}", table.get_highscore());
+
+        let top = table.get_top_scores(10).unwrap();
+        assert_eq!(top.len(), 10);
+        assert!(top.windows(2).all(|pair| pair[0].points >= pair[1].points));
+        assert_eq!(table.get_highscore(), Some(top[0].clone()));
+
+        // Ties on points go to the quicker game.
+        let best = |seconds| HighScore { name: "zzz".to_string(), date: "2021-12-01".to_string(), points: 9999, seconds };
+        table.add(best(300)).unwrap();
+        table.add(best(200)).unwrap();
+        assert_eq!(table.get_highscore(), Some(best(200)));
+        assert_eq!(table.get_player_scores("zzz", 10).unwrap(), vec![best(200), best(300)]);
     }
 
 }
-
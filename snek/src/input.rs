@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::snek::{SnekDirection, SnekId};
+
+/* Something a player can ask for, whichever key or button they pressed. */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Turn(SnekId, SnekDirection),
+    Pause,
+    Restart,
+    QuickSave,
+    QuickLoad,
+    Quit,
+}
+
+fn bound(names: &[String], name: &str) -> bool {
+    names.iter().any(|bound| bound.eq_ignore_ascii_case(name))
+}
+
+fn names(list: &[&str]) -> Vec<String> {
+    list.iter().map(|name| name.to_string()).collect()
+}
+
+/* The keys or buttons that turn a Snek each way.  Any number of them
+ * can turn it the same way.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Steering {
+    pub north: Vec<String>,
+    pub east: Vec<String>,
+    pub south: Vec<String>,
+    pub west: Vec<String>,
+}
+
+impl Steering {
+    pub fn new(north: &[&str], east: &[&str], south: &[&str], west: &[&str]) -> Steering {
+        Steering { north: names(north), east: names(east), south: names(south), west: names(west) }
+    }
+
+    pub fn dir(&self, name: &str) -> Option<SnekDirection> {
+        [
+            (&self.north, SnekDirection::North),
+            (&self.east, SnekDirection::East),
+            (&self.south, SnekDirection::South),
+            (&self.west, SnekDirection::West),
+        ].iter().find(|(names, _)| bound(names, name)).map(|(_, dir)| *dir)
+    }
+}
+
+/* Steering as written in a settings file, where any direction left out
+ * keeps the keys it had.
+ */
+#[derive(Deserialize)]
+struct SteeringFile {
+    north: Option<Vec<String>>,
+    east: Option<Vec<String>>,
+    south: Option<Vec<String>>,
+    west: Option<Vec<String>>,
+}
+
+impl SteeringFile {
+    fn over(self, base: Steering) -> Steering {
+        Steering {
+            north: self.north.unwrap_or(base.north),
+            east: self.east.unwrap_or(base.east),
+            south: self.south.unwrap_or(base.south),
+            west: self.west.unwrap_or(base.west),
+        }
+    }
+}
+
+/* The keyboard layouts bindings can start from.  Classic has arrows,
+ * WASD, IJKL and the keypad for up to four players; vim gives player
+ * one HJKL as well as the arrows, which leaves only WASD for a second
+ * player as IJKL would clash.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyLayout {
+    #[default]
+    Classic,
+    Vim,
+}
+
+/* Which keys do what.  Keys go by the names SDL gives them, such as
+ * "Up", "W", "Space", "Keypad 8" or "F5", ignoring case; the terminal
+ * frontend names its keys to match.  A settings file picks a layout
+ * and then rebinds whatever it likes, the rest coming from the layout.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "KeyBindingsFile")]
+pub struct KeyBindings {
+    pub layout: KeyLayout,
+    pub pause: Vec<String>,
+    pub restart: Vec<String>,
+    pub quit: Vec<String>,
+    pub quicksave: Vec<String>,
+    pub quickload: Vec<String>,
+    /* Steering for each player in turn. */
+    pub players: Vec<Steering>,
+}
+
+/* KeyBindings as written in a settings file, where anything left out
+ * comes from the layout.  The nth [[keys.players]] entry rebinds the
+ * nth player's steering, one direction at a time.
+ */
+#[derive(Deserialize)]
+struct KeyBindingsFile {
+    #[serde(default)]
+    layout: KeyLayout,
+    pause: Option<Vec<String>>,
+    restart: Option<Vec<String>>,
+    quit: Option<Vec<String>>,
+    quicksave: Option<Vec<String>>,
+    quickload: Option<Vec<String>>,
+    #[serde(default)]
+    players: Vec<SteeringFile>,
+}
+
+impl From<KeyBindingsFile> for KeyBindings {
+    fn from(file: KeyBindingsFile) -> KeyBindings {
+        let base = KeyBindings::new(file.layout);
+        let mut players = base.players;
+        for (id, steering) in file.players.into_iter().enumerate() {
+            if players.len() <= id {
+                players.push(Steering::default());
+            }
+            players[id] = steering.over(std::mem::take(&mut players[id]));
+        }
+        KeyBindings {
+            layout: file.layout,
+            pause: file.pause.unwrap_or(base.pause),
+            restart: file.restart.unwrap_or(base.restart),
+            quit: file.quit.unwrap_or(base.quit),
+            quicksave: file.quicksave.unwrap_or(base.quicksave),
+            quickload: file.quickload.unwrap_or(base.quickload),
+            players,
+        }
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings::new(KeyLayout::Classic)
+    }
+}
+
+impl KeyBindings {
+    pub fn new(layout: KeyLayout) -> KeyBindings {
+        let wasd = Steering::new(&["W"], &["D"], &["S"], &["A"]);
+        let players = match layout {
+            KeyLayout::Classic => vec![
+                Steering::new(&["Up"], &["Right"], &["Down"], &["Left"]),
+                wasd,
+                Steering::new(&["I"], &["L"], &["K"], &["J"]),
+                Steering::new(&["Keypad 8"], &["Keypad 6"], &["Keypad 5"], &["Keypad 4"]),
+            ],
+            KeyLayout::Vim => vec![
+                Steering::new(&["K", "Up"], &["L", "Right"], &["J", "Down"], &["H", "Left"]),
+                wasd,
+            ],
+        };
+        KeyBindings {
+            layout,
+            pause: names(&["P", "Space"]),
+            restart: names(&["R"]),
+            quit: names(&["Q", "Escape"]),
+            quicksave: names(&["F5"]),
+            quickload: names(&["F9"]),
+            players,
+        }
+    }
+
+    /* The bindings for a game with this many players.  With only one
+     * player, every player's steering keys steer player one, so the
+     * arrows and WASD both work.
+     */
+    pub fn for_players(&self, count: usize) -> KeyBindings {
+        let mut keys = self.clone();
+        if count == 1 {
+            let mut steering = Steering::default();
+            for player in &self.players {
+                steering.north.extend(player.north.iter().cloned());
+                steering.east.extend(player.east.iter().cloned());
+                steering.south.extend(player.south.iter().cloned());
+                steering.west.extend(player.west.iter().cloned());
+            }
+            keys.players = vec![steering];
+        }
+        keys
+    }
+
+    /* What the named key is bound to, if anything.  If a key is bound
+     * twice, the game keys win over steering.
+     */
+    pub fn action(&self, key: &str) -> Option<Action> {
+        if bound(&self.quit, key) {
+            Some(Action::Quit)
+        } else if bound(&self.pause, key) {
+            Some(Action::Pause)
+        } else if bound(&self.restart, key) {
+            Some(Action::Restart)
+        } else if bound(&self.quicksave, key) {
+            Some(Action::QuickSave)
+        } else if bound(&self.quickload, key) {
+            Some(Action::QuickLoad)
+        } else {
+            self.players.iter().enumerate().find_map(|(id, keys)| {
+                keys.dir(key).map(|dir| Action::Turn(id, dir))
+            })
+        }
+    }
+}
+
+/* What game controller buttons do, by the names SDL gives them
+ * ("dpup", "start", "back", "a" and so on).  Every controller steers
+ * the same way; which player it steers depends on when it turned up.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PadBindings {
+    pub pause: Vec<String>,
+    pub restart: Vec<String>,
+    pub quit: Vec<String>,
+    pub steer: Steering,
+}
+
+impl Default for PadBindings {
+    fn default() -> PadBindings {
+        PadBindings {
+            pause: names(&["start"]),
+            restart: names(&["back"]),
+            quit: Vec::new(),
+            steer: Steering::new(&["dpup"], &["dpright"], &["dpdown"], &["dpleft"]),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StickAxis {
+    X,
+    Y,
+}
+
+/* How far, out of 32767, the stick has to lean before it counts. */
+const STICK_DEADZONE: i32 = 16_000;
+
+#[derive(Debug, Default)]
+struct Stick {
+    x: i16,
+    y: i16,
+    dir: Option<SnekDirection>,
+}
+
+/* Game controllers, each steering one player.  The first controller
+ * steers player one, the next player two, and so on; unplugging one
+ * frees its player for the next controller to turn up.  Controllers
+ * are known by whatever id the frontend gives them, such as SDL's
+ * instance ids.
+ */
+#[derive(Debug, Default)]
+pub struct Gamepads {
+    bindings: PadBindings,
+    /* The controller steering each player, if it's still plugged in. */
+    players: Vec<Option<u32>>,
+    sticks: HashMap<u32, Stick>,
+}
+
+impl Gamepads {
+    pub fn new(bindings: PadBindings) -> Gamepads {
+        Gamepads { bindings, ..Gamepads::default() }
+    }
+
+    /* The player pad steers, handing it the first free one if it's new. */
+    pub fn player(&mut self, pad: u32) -> SnekId {
+        if let Some(id) = self.players.iter().position(|p| *p == Some(pad)) {
+            return id;
+        }
+        match self.players.iter().position(Option::is_none) {
+            Some(id) => {
+                self.players[id] = Some(pad);
+                id
+            },
+            None => {
+                self.players.push(Some(pad));
+                self.players.len() - 1
+            },
+        }
+    }
+
+    pub fn remove(&mut self, pad: u32) {
+        for slot in self.players.iter_mut().filter(|p| **p == Some(pad)) {
+            *slot = None;
+        }
+        self.sticks.remove(&pad);
+    }
+
+    pub fn button(&mut self, pad: u32, button: &str) -> Option<Action> {
+        let id = self.player(pad);
+        let bindings = &self.bindings;
+        if bound(&bindings.quit, button) {
+            Some(Action::Quit)
+        } else if bound(&bindings.pause, button) {
+            Some(Action::Pause)
+        } else if bound(&bindings.restart, button) {
+            Some(Action::Restart)
+        } else {
+            bindings.steer.dir(button).map(|dir| Action::Turn(id, dir))
+        }
+    }
+
+    /* The stick steers whichever way it leans furthest.  Sticks report
+     * every twitch, so it only turns Snek when it swings round to a
+     * new direction, or comes back out of the middle.
+     */
+    pub fn stick(&mut self, pad: u32, axis: StickAxis, value: i16) -> Option<Action> {
+        let id = self.player(pad);
+        let stick = self.sticks.entry(pad).or_default();
+        match axis {
+            StickAxis::X => stick.x = value,
+            StickAxis::Y => stick.y = value,
+        }
+        let (x, y) = (stick.x as i32, stick.y as i32);
+        let dir = if x.abs().max(y.abs()) < STICK_DEADZONE {
+            None
+        } else if x.abs() > y.abs() {
+            Some(if x > 0 { SnekDirection::East } else { SnekDirection::West })
+        } else {
+            // Down is positive.
+            Some(if y > 0 { SnekDirection::South } else { SnekDirection::North })
+        };
+        if dir == stick.dir {
+            return None;
+        }
+        stick.dir = dir;
+        dir.map(|dir| Action::Turn(id, dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_bindings() {
+        let keys = KeyBindings::default();
+        assert_eq!(keys.action("Left"), Some(Action::Turn(0, SnekDirection::West)));
+        assert_eq!(keys.action("s"), Some(Action::Turn(1, SnekDirection::South)));
+        assert_eq!(keys.action("Keypad 8"), Some(Action::Turn(3, SnekDirection::North)));
+        assert_eq!(keys.action("space"), Some(Action::Pause));
+        assert_eq!(keys.action("R"), Some(Action::Restart));
+        assert_eq!(keys.action("F9"), Some(Action::QuickLoad));
+        assert_eq!(keys.action("X"), None);
+
+        let keys = KeyBindings { quit: vec!["W".to_string()], ..KeyBindings::default() };
+        assert_eq!(keys.action("W"), Some(Action::Quit));
+    }
+
+    #[test]
+    fn test_single_player_bindings() {
+        let keys = KeyBindings::default().for_players(1);
+        assert_eq!(keys.action("Left"), Some(Action::Turn(0, SnekDirection::West)));
+        assert_eq!(keys.action("s"), Some(Action::Turn(0, SnekDirection::South)));
+        assert_eq!(keys.action("I"), Some(Action::Turn(0, SnekDirection::North)));
+        assert_eq!(keys.action("P"), Some(Action::Pause));
+        assert_eq!(KeyBindings::default().for_players(2), KeyBindings::default());
+    }
+
+    #[test]
+    fn test_vim_bindings() {
+        let keys = KeyBindings::new(KeyLayout::Vim);
+        assert_eq!(keys.action("h"), Some(Action::Turn(0, SnekDirection::West)));
+        assert_eq!(keys.action("K"), Some(Action::Turn(0, SnekDirection::North)));
+        assert_eq!(keys.action("Up"), Some(Action::Turn(0, SnekDirection::North)));
+        assert_eq!(keys.action("D"), Some(Action::Turn(1, SnekDirection::East)));
+        assert_eq!(keys.action("I"), None);
+    }
+
+    #[test]
+    fn test_partial_rebinding() {
+        // Only player one's north is rebound; the rest is still classic.
+        let keys: KeyBindings = toml::from_str("[[players]]\nnorth = [\"Home\"]\n").unwrap();
+        let classic = KeyBindings::default();
+        assert_eq!(keys.action("Home"), Some(Action::Turn(0, SnekDirection::North)));
+        assert_eq!(keys.action("Up"), None);
+        assert_eq!(keys.action("Left"), Some(Action::Turn(0, SnekDirection::West)));
+        assert_eq!(keys.players[1..], classic.players[1..]);
+        assert_eq!(keys.quit, classic.quit);
+    }
+
+    #[test]
+    fn test_gamepads() {
+        let mut pads = Gamepads::new(PadBindings::default());
+        assert_eq!(pads.button(7, "dpleft"), Some(Action::Turn(0, SnekDirection::West)));
+        assert_eq!(pads.button(9, "dpup"), Some(Action::Turn(1, SnekDirection::North)));
+        assert_eq!(pads.button(9, "start"), Some(Action::Pause));
+        assert_eq!(pads.button(9, "a"), None);
+
+        // The stick turns once per swing, not on every twitch.
+        assert_eq!(pads.stick(7, StickAxis::X, 8_000), None);
+        assert_eq!(pads.stick(7, StickAxis::X, 30_000), Some(Action::Turn(0, SnekDirection::East)));
+        assert_eq!(pads.stick(7, StickAxis::X, 31_000), None);
+        assert_eq!(pads.stick(7, StickAxis::Y, -32_768), Some(Action::Turn(0, SnekDirection::North)));
+        assert_eq!(pads.stick(7, StickAxis::Y, 0), Some(Action::Turn(0, SnekDirection::East)));
+        assert_eq!(pads.stick(7, StickAxis::X, 0), None);
+        assert_eq!(pads.stick(7, StickAxis::X, 30_000), Some(Action::Turn(0, SnekDirection::East)));
+
+        // A new controller takes over the first unplugged one's player.
+        pads.remove(7);
+        assert_eq!(pads.button(12, "dpdown"), Some(Action::Turn(0, SnekDirection::South)));
+        assert_eq!(pads.player(9), 1);
+    }
+}
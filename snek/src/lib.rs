@@ -1,2 +1,21 @@
+pub mod board;
+pub mod clock;
+pub mod controller;
+pub mod env;
 pub mod highscores;
+pub mod input;
+pub mod map;
+pub mod render;
+pub mod replay;
+pub mod rules;
+pub mod save;
+#[cfg(feature = "sdl")]
+pub mod sdl;
+#[cfg(feature = "sdl")]
+pub mod sdl_input;
+pub mod settings;
+pub mod sim;
 pub mod snek;
+#[cfg(feature = "term")]
+pub mod term;
+pub mod theme;
@@ -1,91 +1,747 @@
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use std::time::Duration;
+// Without a frontend only the headless commands are any use.
+#![cfg_attr(not(any(feature = "sdl", feature = "term")), allow(dead_code, unused_variables))]
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
-// use snek;
-// mod snek;
-use snek::snek::{SnekDirection, SnekGame};
+use snek::controller::{collect_turns, BotKind, HumanController, SnekController};
+use snek::highscores::{self, HighScore, HighScoreTable};
+use snek::input::Action;
+use snek::map::SnekMap;
+use snek::replay::{Replay, ReplayPlayer, ReplayRecorder};
+use snek::rules::GameRules;
+use snek::save;
+use snek::settings::{self, Settings, TextSettings};
+use snek::sim::{self, SimConfig};
+use snek::snek::SnekGame;
+use snek::theme::TextTheme;
 
-fn main() -> Result<(), String> {
-    let sdl_context = sdl2::init()?;
-    let video_subsystem = sdl_context.video()?;
-
-    video_subsystem.gl_attr().set_context_profile(sdl2::video::GLProfile::GLES);
-    video_subsystem.gl_attr().set_context_major_version(2);
-    video_subsystem.gl_attr().set_context_minor_version(0);
-
-    let window = video_subsystem
-        .window("Snek!", 1024, 768)
-        .position_centered()
-        .opengl()
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    let mut canvas = window
-            .into_canvas()
-            .build()
-            .map_err(|e| e.to_string())?;
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
-    canvas.present();
-
-    let mut event_pump = sdl_context.event_pump()?;
-    // FIXME: dimensions on board should be constant and scale up to canvas resolution.
-    // FIXME: for now, they're fixed and we assume we're at 1024x768.
-    let mut game = SnekGame::new(32, 24);
-    let mut ticks = 0;
-    'running: loop {
-        const FRAMES_PER_SEC: u32 = 10;
-        for event in event_pump.poll_iter() {
+#[derive(Parser)]
+#[clap(name = "snek", version, about = "Snek eats Snekberries.  Runs `play` if no command is given.")]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[clap(about = "Play a game")]
+    Play(PlayArgs),
+    #[clap(about = "List the high scores")]
+    Scores(ScoresArgs),
+    #[clap(about = "Watch a recorded game")]
+    Replay(ReplayArgs),
+    #[clap(about = "Play a batch of headless games with a bot and summarise them")]
+    Sim(SimArgs),
+    #[clap(about = "Show where the settings file lives and what's in effect")]
+    Settings,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum RendererKind {
+    Sdl,
+    Term,
+}
+
+impl Default for RendererKind {
+    fn default() -> RendererKind {
+        if cfg!(feature = "sdl") {
+            RendererKind::Sdl
+        } else {
+            RendererKind::Term
+        }
+    }
+}
+
+/* How the terminal renderer draws the board, if not as the settings
+ * say.  Each setting has a flag to turn it on and one to turn it off,
+ * and whichever comes last on the command line wins.
+ */
+#[derive(Args)]
+struct TextArgs {
+    #[clap(long, overrides_with = "no-ascii", help = "Draw the terminal board in plain ASCII, without colour")]
+    ascii: bool,
+    #[clap(long, overrides_with = "ascii", help = "Draw the terminal board with the usual glyphs")]
+    no_ascii: bool,
+    #[clap(long, overrides_with = "no-color", help = "Draw the terminal board in colour")]
+    color: bool,
+    #[clap(long, overrides_with = "color", help = "Draw the terminal board without colour")]
+    no_color: bool,
+    #[clap(long, overrides_with = "no-border", help = "Draw a wall round the terminal board")]
+    border: bool,
+    #[clap(long, overrides_with = "border", help = "Draw the terminal board without a wall round it")]
+    no_border: bool,
+}
+
+/* The setting a pair of on and off flags asks for, if either was given. */
+fn toggle(on: bool, off: bool, setting: bool) -> bool {
+    match (on, off) {
+        (true, _) => true,
+        (_, true) => false,
+        _ => setting,
+    }
+}
+
+impl TextArgs {
+    fn settings(&self, settings: &TextSettings) -> TextSettings {
+        TextSettings {
+            ascii: toggle(self.ascii, self.no_ascii, settings.ascii),
+            color: toggle(self.color, self.no_color, settings.color),
+            border: toggle(self.border, self.no_border, settings.border),
+        }
+    }
+
+    fn theme(&self, settings: &TextSettings) -> TextTheme {
+        self.settings(settings).theme()
+    }
+}
+
+#[derive(Args)]
+struct PlayArgs {
+    #[clap(long, help = "Board width in cells")]
+    width: Option<usize>,
+    #[clap(long, help = "Board height in cells")]
+    height: Option<usize>,
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..), help = "Ticks per second [default: 10]")]
+    fps: Option<u32>,
+    #[clap(long, help = "Seed for where things spawn; random if not given")]
+    seed: Option<u64>,
+    #[clap(long, value_name = "FILE", help = "Game rules as JSON")]
+    rules: Option<PathBuf>,
+    #[clap(long, value_enum, default_value_t)]
+    renderer: RendererKind,
+    #[clap(flatten)]
+    text: TextArgs,
+    #[clap(long, value_parser = clap::value_parser!(u8).range(1..=4), help = "Number of players, 1-4")]
+    players: Option<u8>,
+    #[clap(long, help = "Let a bot steer player one: path or cycle")]
+    bot: Option<BotKind>,
+    #[clap(long, help = "Leave the board edges open, leading round to the other side")]
+    wrap: bool,
+    #[clap(long, value_name = "FILE", conflicts_with_all = &["width", "height"], help = "Play on an arena from a map file")]
+    map: Option<PathBuf>,
+    #[clap(long, value_name = "FILE", help = "Record the game for `snek replay`")]
+    record: Option<PathBuf>,
+    #[clap(long, help = "Start afresh instead of resuming the last unfinished game")]
+    new: bool,
+    #[clap(long, help = "Initials to put on the high score table [default: AAA]")]
+    initials: Option<String>,
+}
+
+impl PlayArgs {
+    /* Whether any option asks for a particular kind of game, which an
+     * old game being resumed wouldn't be.
+     */
+    fn shapes_game(&self) -> bool {
+        self.seed.is_some()
+            || self.width.is_some()
+            || self.height.is_some()
+            || self.rules.is_some()
+            || self.map.is_some()
+            || self.players.is_some()
+            || self.wrap
+    }
+}
+
+#[derive(Args)]
+struct ScoresArgs {
+    #[clap(long, default_value_t = 10, help = "How many scores to list")]
+    top: usize,
+    #[clap(long, value_name = "INITIALS", help = "Only list this player's scores")]
+    player: Option<String>,
+    #[clap(long, value_name = "FILE", help = "High score database to read")]
+    db: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    #[clap(value_name = "FILE")]
+    file: PathBuf,
+    #[clap(long, value_enum, default_value_t)]
+    renderer: RendererKind,
+    #[clap(flatten)]
+    text: TextArgs,
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..), help = "Ticks per second [default: 10]")]
+    fps: Option<u32>,
+}
+
+#[derive(Args)]
+struct SimArgs {
+    #[clap(long, default_value = "path", help = "Bot to play with: path or cycle")]
+    bot: BotKind,
+    #[clap(long, default_value_t = 100)]
+    games: u64,
+    #[clap(long, default_value_t = 0, help = "Seed of the first game; game n uses seed + n")]
+    seed: u64,
+    #[clap(long, default_value_t = 10_000, help = "Stop games that run this long")]
+    max_ticks: u64,
+    #[clap(long, value_name = "FILE", help = "Game rules as JSON")]
+    rules: Option<PathBuf>,
+    #[clap(long, value_name = "FILE")]
+    map: Option<PathBuf>,
+    #[clap(long)]
+    wrap: bool,
+    #[clap(long, help = "Print the report as JSON")]
+    json: bool,
+}
+
+/* A window or terminal to play in. */
+trait Frontend {
+    fn draw(&mut self, game: &SnekGame, paused: bool) -> Result<(), String>;
+    /* Wait up to timeout for the player to do something. */
+    fn poll(&mut self, timeout: Duration) -> Result<Option<Action>, String>;
+    /* Tell the player something, such as whether a quicksave worked,
+     * until there's something else to tell them.
+     */
+    fn show_message(&mut self, message: &str) -> Result<(), String>;
+}
+
+#[cfg(feature = "sdl")]
+mod sdl_frontend {
+    use std::time::Duration;
+    use sdl2::controller::GameController;
+    use sdl2::event::Event;
+    use sdl2::pixels::Color;
+    use sdl2::{EventPump, GameControllerSubsystem};
+
+    use snek::input::{Action, KeyBindings};
+    use snek::render::Renderer;
+    use snek::sdl::SdlRenderer;
+    use snek::sdl_input::SdlInput;
+    use snek::settings::Settings;
+    use snek::snek::SnekGame;
+    use super::Frontend;
+
+    /* Pixels per board cell. */
+    const CELL_SIZE: u32 = 32;
+
+    pub struct SdlFrontend {
+        renderer: SdlRenderer,
+        events: EventPump,
+        input: SdlInput,
+        /* None if SDL couldn't find any controller support. */
+        controllers: Option<GameControllerSubsystem>,
+        /* A controller only sends events while it's held open. */
+        pads: Vec<GameController>,
+    }
+
+    impl SdlFrontend {
+        /* Open a window just big enough for game's board. */
+        pub fn new(game: &SnekGame, settings: &Settings, keys: KeyBindings) -> Result<SdlFrontend, String> {
+            let sdl_context = sdl2::init()?;
+            let video_subsystem = sdl_context.video()?;
+
+            video_subsystem.gl_attr().set_context_profile(sdl2::video::GLProfile::GLES);
+            video_subsystem.gl_attr().set_context_major_version(2);
+            video_subsystem.gl_attr().set_context_minor_version(0);
+
+            let window = video_subsystem
+                .window("Snek!", game.xsize() as u32 * CELL_SIZE, game.ysize() as u32 * CELL_SIZE)
+                .position_centered()
+                .opengl()
+                .build()
+                .map_err(|e| e.to_string())?;
+
+            let mut canvas = window
+                    .into_canvas()
+                    .build()
+                    .map_err(|e| e.to_string())?;
+            canvas.set_draw_color(Color::RGB(0, 0, 0));
+            canvas.clear();
+            canvas.present();
+
+            Ok(SdlFrontend {
+                renderer: SdlRenderer::new(canvas).palette(settings.colors.clone()),
+                events: sdl_context.event_pump()?,
+                input: SdlInput::new(keys, settings.pad.clone()),
+                // SDL reports the controllers already plugged in as if
+                // they'd just arrived, so poll() opens those too.
+                controllers: sdl_context.game_controller().ok(),
+                pads: Vec::new(),
+            })
+        }
+    }
+
+    impl Frontend for SdlFrontend {
+        fn draw(&mut self, game: &SnekGame, _paused: bool) -> Result<(), String> {
+            self.renderer.draw(game)
+        }
+
+        fn poll(&mut self, timeout: Duration) -> Result<Option<Action>, String> {
+            let event = match self.events.wait_event_timeout(timeout.as_millis() as u32) {
+                Some(event) => event,
+                None => return Ok(None),
+            };
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => break 'running,
-                
-                Event::KeyDown {
-                    keycode: Some(Keycode::Up),
-                    ..
-                } => { game.set_snekdir(SnekDirection::North); },
-
-                Event::KeyDown {
-                    keycode: Some(Keycode::Right),
-                    ..
-                } => { game.set_snekdir(SnekDirection::East); },
-
-                Event::KeyDown {
-                    keycode: Some(Keycode::Left),
-                    ..
-                } => { game.set_snekdir(SnekDirection::West); },
-
-                Event::KeyDown {
-                    keycode: Some(Keycode::Down),
-                    ..
-                } => { game.set_snekdir(SnekDirection::South); },
-
-                _ => {}
+                Event::ControllerDeviceAdded { which, .. } => {
+                    if let Some(pad) = self.controllers.as_ref().and_then(|c| c.open(which).ok()) {
+                        self.input.add_pad(pad.instance_id());
+                        self.pads.push(pad);
+                    }
+                },
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.pads.retain(|pad| pad.instance_id() != which);
+                },
+                _ => {},
             }
+            Ok(self.input.event(&event))
+        }
+
+        /* There's no text in the window, so the message goes in its title. */
+        fn show_message(&mut self, message: &str) -> Result<(), String> {
+            self.renderer.canvas().window_mut()
+                .set_title(&format!("Snek!  {}", message))
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+#[cfg(feature = "term")]
+mod term_frontend {
+    use std::io::{self, Stdout};
+    use std::time::Duration;
+
+    use snek::input::{Action, KeyBindings};
+    use snek::render::Renderer;
+    use snek::snek::SnekGame;
+    use snek::term::{poll_input, TermRenderer, TermSession};
+    use snek::theme::TextTheme;
+    use super::Frontend;
+
+    /* The terminal can't tell the keypad from the number keys, so the
+     * fourth player's default keys don't work there.
+     */
+    pub const MAX_PLAYERS: usize = 3;
+
+    pub struct TermFrontend {
+        renderer: TermRenderer<Stdout>,
+        keys: KeyBindings,
+        _session: TermSession,
+    }
+
+    impl TermFrontend {
+        pub fn new(theme: TextTheme, keys: KeyBindings) -> Result<TermFrontend, String> {
+            Ok(TermFrontend {
+                _session: TermSession::start()?,
+                renderer: TermRenderer::new(io::stdout()).theme(theme).keys(keys.clone()),
+                keys,
+            })
         }
+    }
 
-        
-        canvas.clear();
-        game.step();
-        game.draw(&mut canvas);
-        canvas.present();
+    impl Frontend for TermFrontend {
+        fn draw(&mut self, game: &SnekGame, paused: bool) -> Result<(), String> {
+            self.renderer.set_paused(paused);
+            self.renderer.draw(game)
+        }
 
-        println!("{}", game);
+        fn poll(&mut self, timeout: Duration) -> Result<Option<Action>, String> {
+            poll_input(&self.keys, timeout)
+        }
 
-        if game.game_over {
-            println!("Game Over!");
-            // break 'running;
+        fn show_message(&mut self, message: &str) -> Result<(), String> {
+            self.renderer.set_message(Some(message.to_string()));
+            Ok(())
         }
-        
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / FRAMES_PER_SEC));
-        // ::std::thread::sleep(Duration::new(0, 500_000_000)); // 1 second delay for debug
-        ticks += 1;
     }
+}
+
+fn open_frontend(
+    kind: RendererKind,
+    text: &TextArgs,
+    settings: &Settings,
+    game: &SnekGame,
+) -> Result<Box<dyn Frontend>, String> {
+    let keys = settings.keys.for_players(game.sneks().len());
+    match kind {
+        #[cfg(feature = "sdl")]
+        RendererKind::Sdl => Ok(Box::new(sdl_frontend::SdlFrontend::new(game, settings, keys)?)),
+        #[cfg(feature = "term")]
+        RendererKind::Term => {
+            if game.sneks().len() > term_frontend::MAX_PLAYERS {
+                return Err(format!("the terminal only has keys for {} players", term_frontend::MAX_PLAYERS));
+            }
+            let theme = text.theme(&settings.text);
+            Ok(Box::new(term_frontend::TermFrontend::new(theme, keys)?))
+        },
+        #[allow(unreachable_patterns)]
+        _ => Err(format!("snek was built without the {:?} renderer", kind).to_lowercase()),
+    }
+}
 
+/* Where a game's turns come from besides the players, whether it can
+ * be saved part way through, and how to start another.
+ */
+struct Session<'a> {
+    bot: Option<Box<dyn SnekController>>,
+    recorder: Option<ReplayRecorder>,
+    player: Option<ReplayPlayer>,
+    fps: u32,
+    /* Sets up a fresh game when the player asks to restart; None if
+     * they can't.
+     */
+    restart: Option<Box<dyn FnMut() -> Result<SnekGame, String> + 'a>>,
+    /* Games played to the end before a restart. */
+    finished: Vec<SnekGame>,
+}
+
+impl Session<'_> {
+    /* Saving mid-recording would leave a replay that can't be played
+     * back, and there's nothing to save while watching one.
+     */
+    fn can_save(&self) -> bool {
+        self.recorder.is_none() && self.player.is_none()
+    }
+}
+
+/* Play until the player quits. */
+fn run(frontend: &mut dyn Frontend, game: &mut SnekGame, session: &mut Session) -> Result<(), String> {
+    let mut humans: Vec<HumanController> = game.sneks().iter().map(|_| HumanController::new()).collect();
+    let mut paused = false;
+    'running: loop {
+        frontend.draw(game, paused)?;
+
+        // Take input until it's time for the next tick.  Speed berries
+        // change the tick rate for a while.
+        let percent = game.tick_rate_percent().max(1) as u64;
+        let deadline = Instant::now() + Duration::from_nanos(100_000_000_000 / (session.fps as u64 * percent));
+        while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+            match frontend.poll(timeout)? {
+                Some(Action::Quit) => break 'running,
+                Some(Action::Pause) if !game.is_over() => {
+                    paused = !paused;
+                    if !paused {
+                        game.resume_clock();
+                    }
+                    frontend.draw(game, paused)?;
+                },
+                // Keyboard steering is ignored while watching a replay.
+                Some(Action::Turn(id, dir)) if !paused && session.player.is_none() => {
+                    if let Some(human) = humans.get_mut(id) {
+                        human.push(dir);
+                    }
+                },
+                Some(Action::Restart) => {
+                    if let Some(restart) = session.restart.as_mut() {
+                        let old = std::mem::replace(game, restart()?);
+                        if old.is_over() {
+                            session.finished.push(old);
+                        }
+                        // A recording only ever holds the latest game.
+                        if let Some(recorder) = session.recorder.as_mut() {
+                            *recorder = ReplayRecorder::new(game);
+                        }
+                        humans = game.sneks().iter().map(|_| HumanController::new()).collect();
+                        paused = false;
+                        frontend.draw(game, paused)?;
+                    }
+                },
+                Some(Action::QuickSave) if session.can_save() => {
+                    if let Some(path) = save::quicksave_path() {
+                        let message = match save::save(game, &path) {
+                            Ok(()) => format!("Saved to {}", path.display()),
+                            Err(e) => format!("Couldn't save: {}", e),
+                        };
+                        frontend.show_message(&message)?;
+                        frontend.draw(game, paused)?;
+                    }
+                },
+                Some(Action::QuickLoad) if session.can_save() => {
+                    if let Some(path) = save::quicksave_path() {
+                        match save::load(&path) {
+                            Ok(loaded) => {
+                                *game = loaded;
+                                humans = game.sneks().iter().map(|_| HumanController::new()).collect();
+                                frontend.show_message(&format!("Loaded {}", path.display()))?;
+                            },
+                            Err(e) => frontend.show_message(&format!("Couldn't load {}: {}", path.display(), e))?,
+                        }
+                        frontend.draw(game, paused)?;
+                    }
+                },
+                _ => {},
+            }
+        }
+        if paused {
+            continue;
+        }
+
+        if session.player.is_none() {
+            let mut controllers: Vec<&mut dyn SnekController> = humans.iter_mut()
+                .map(|human| human as &mut dyn SnekController)
+                .collect();
+            if let Some(bot) = session.bot.as_mut() {
+                controllers[0] = bot.as_mut();
+            }
+            for (id, dir) in collect_turns(game, &mut controllers) {
+                match session.recorder.as_mut() {
+                    Some(recorder) => recorder.set_snek_dir(game, id, dir),
+                    None => game.set_snek_dir(id, dir),
+                }
+            }
+        }
+
+        match (session.player.as_mut(), session.recorder.as_mut()) {
+            (Some(player), _) => { player.step(game); },
+            (None, Some(recorder)) if !game.is_over() => { recorder.step(game); },
+            _ => { game.step(); },
+        }
+    }
+    Ok(())
+}
+
+fn load_rules(file: &Path) -> Result<GameRules, String> {
+    let reader = BufReader::new(File::open(file).map_err(|e| format!("{}: {}", file.display(), e))?);
+    let rules: GameRules = serde_json::from_reader(reader).map_err(|e| format!("{}: {}", file.display(), e))?;
+    rules.validate().map_err(|e| format!("{}: {}", file.display(), e))?;
+    Ok(rules)
+}
+
+fn load_map(file: &Path) -> Result<SnekMap, String> {
+    SnekMap::load(file).map_err(|e| format!("{}: {}", file.display(), e))
+}
+
+fn new_game(args: &PlayArgs, settings: &Settings) -> Result<SnekGame, String> {
+    let mut rules = match &args.rules {
+        Some(file) => load_rules(file)?,
+        None => GameRules::new(),
+    };
+    if args.wrap {
+        rules = rules.wrap(true);
+    }
+    let seed = args.seed.unwrap_or_else(rand::random);
+    if let Some(file) = &args.map {
+        let map = load_map(file)?;
+        return Ok(SnekGame::on_map(&map, rules, seed));
+    }
+
+    // The terminal's board is wider than it's tall, to make up for
+    // characters being taller than they're wide.
+    let (xsize, ysize) = match (&args.rules, args.renderer) {
+        (Some(_), _) => (rules.xsize, rules.ysize),
+        (None, RendererKind::Sdl) => (settings.width.unwrap_or(32), settings.height.unwrap_or(24)),
+        (None, RendererKind::Term) => (settings.width.unwrap_or(40), settings.height.unwrap_or(18)),
+    };
+    rules = rules.board_size(args.width.unwrap_or(xsize), args.height.unwrap_or(ysize));
+    if args.players.is_some() || args.rules.is_none() {
+        rules = rules.players(args.players.unwrap_or(1) as usize);
+    }
+    rules.validate()?;
+    Ok(SnekGame::with_rules(rules, seed))
+}
+
+fn play(args: PlayArgs, settings: &Settings) -> Result<(), String> {
+    // Pick up where the last game was quit, unless asked not to or asked
+    // for a different game.  Either way the new game takes the unfinished
+    // one's place when it's quit or over.
+    let resumed = match (&args.record, args.new, save::autosave_path()) {
+        (None, false, Some(path)) if path.exists() && args.shapes_game() => {
+            println!("Not resuming {}: starting the new game asked for, which will replace it", path.display());
+            None
+        },
+        (None, false, Some(path)) if path.exists() => match save::load(&path) {
+            Ok(game) => Some(game),
+            Err(e) => {
+                println!("Not resuming {}: {}; starting a new game, which will replace it", path.display(), e);
+                None
+            },
+        },
+        _ => None,
+    };
+    let mut game = match resumed {
+        Some(game) => game,
+        None => new_game(&args, settings)?,
+    };
+    let mut session = Session {
+        bot: args.bot.map(|kind| kind.controller()),
+        recorder: args.record.as_ref().map(|_| ReplayRecorder::new(&game)),
+        player: None,
+        fps: args.fps.unwrap_or(settings.fps),
+        restart: Some(Box::new(|| new_game(&args, settings))),
+        finished: Vec::new(),
+    };
+
+    let mut frontend = open_frontend(args.renderer, &args.text, settings, &game)?;
+    run(frontend.as_mut(), &mut game, &mut session)?;
+    // Put the terminal back before saying anything more.
+    drop(frontend);
+
+    if let (Some(file), Some(recorder)) = (&args.record, &session.recorder) {
+        recorder.replay().save(file).map_err(|e| e.to_string())?;
+    }
+
+    // Keep an unfinished game for next time, and drop a finished one
+    // so it isn't resumed.
+    if let (true, Some(path)) = (session.can_save(), save::autosave_path()) {
+        if game.is_over() {
+            let _ = std::fs::remove_file(&path);
+        } else {
+            save::save(&game, &path).map_err(|e| e.to_string())?;
+        }
+    }
+
+    // Only finished, single player games played by a person count.
+    let scored: Vec<&SnekGame> = session.finished.iter()
+        .chain(Some(&game))
+        .filter(|game| game.is_over() && game.sneks().len() == 1)
+        .collect();
+    if !scored.is_empty() && session.bot.is_none() {
+        if let Some(path) = settings.highscore_db.clone().or_else(highscores::default_path) {
+            let initials = args.initials.as_ref().unwrap_or(&settings.initials);
+            let table = HighScoreTable::open(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            for game in scored {
+                table.add(HighScore::from_game(initials, game)).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn scores(args: ScoresArgs, settings: &Settings) -> Result<(), String> {
+    let path = args.db
+        .or_else(|| settings.highscore_db.clone())
+        .or_else(highscores::default_path)
+        .ok_or("no high score database")?;
+    let table = HighScoreTable::open(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let scores = match &args.player {
+        Some(name) => table.get_player_scores(name, args.top),
+        None => table.get_top_scores(args.top),
+    }.map_err(|e| e.to_string())?;
+    if scores.is_empty() {
+        println!("No scores yet.");
+    }
+    for (rank, score) in scores.iter().enumerate() {
+        println!("{:>3}. {}", rank + 1, score);
+    }
+    Ok(())
+}
+
+fn replay(args: ReplayArgs, settings: &Settings) -> Result<(), String> {
+    let replay = Replay::load(&args.file).map_err(|e| format!("{}: {}", args.file.display(), e))?;
+    let player = ReplayPlayer::new(replay);
+    let mut game = player.new_game();
+    let fps = args.fps.unwrap_or(settings.fps);
+    let mut session = Session {
+        bot: None,
+        recorder: None,
+        player: Some(player),
+        fps,
+        restart: None,
+        finished: Vec::new(),
+    };
+    let mut frontend = open_frontend(args.renderer, &args.text, settings, &game)?;
+    run(frontend.as_mut(), &mut game, &mut session)
+}
+
+fn simulate(args: SimArgs) -> Result<(), String> {
+    let mut config = SimConfig {
+        bot: args.bot,
+        first_seed: args.seed,
+        games: args.games,
+        max_ticks: args.max_ticks,
+        ..SimConfig::default()
+    };
+    if let Some(file) = &args.rules {
+        config.rules = load_rules(file)?;
+    }
+    if let Some(file) = &args.map {
+        config.map = Some(load_map(file)?);
+    }
+    if args.wrap {
+        config.rules.wrap = true;
+    }
+
+    let report = sim::run(&config);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?);
+    } else {
+        print!("{}", report);
+    }
+    Ok(())
+}
+
+/* The settings file's contents, or the defaults if there isn't one. */
+fn load_settings() -> Result<Settings, String> {
+    match settings::path() {
+        Some(path) => settings::load(&path).map_err(|e| format!("{}: {}", path.display(), e)),
+        None => Ok(Settings::default()),
+    }
+}
+
+fn show_settings() -> Result<(), String> {
+    let settings = load_settings()?;
+    match settings::path() {
+        Some(path) => println!("# {}", path.display()),
+        None => println!("# No config directory; using the defaults."),
+    }
+    print!("{}", settings.to_toml());
     Ok(())
 }
+
+fn main() -> Result<(), String> {
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Play(args)) => play(args, &load_settings()?),
+        Some(Command::Scores(args)) => scores(args, &load_settings()?),
+        Some(Command::Replay(args)) => replay(args, &load_settings()?),
+        Some(Command::Sim(args)) => simulate(args),
+        Some(Command::Settings) => show_settings(),
+        None => match Cli::parse_from(["snek", "play"]).command {
+            Some(Command::Play(args)) => play(args, &load_settings()?),
+            _ => unreachable!(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+
+        let cli = Cli::parse_from(["snek", "play", "--width", "20", "--height", "10", "--renderer", "term", "--bot", "cycle"]);
+        match cli.command {
+            Some(Command::Play(args)) => {
+                assert_eq!((args.width, args.height), (Some(20), Some(10)));
+                assert_eq!(args.renderer, RendererKind::Term);
+                let settings = Settings { width: Some(30), height: Some(15), ..Settings::default() };
+                let game = new_game(&args, &settings).unwrap();
+                assert_eq!((game.xsize(), game.ysize()), (20, 10));
+            },
+            _ => panic!("expected play"),
+        }
+        // Settings fill in whatever the command line leaves out.
+        match Cli::parse_from(["snek", "play", "--height", "10", "--renderer", "term"]).command {
+            Some(Command::Play(args)) => {
+                let settings = Settings { width: Some(30), height: Some(15), ..Settings::default() };
+                let game = new_game(&args, &settings).unwrap();
+                assert_eq!((game.xsize(), game.ysize()), (30, 10));
+            },
+            _ => panic!("expected play"),
+        }
+        // Flags win over the settings file, in either direction.
+        match Cli::parse_from(["snek", "play", "--no-ascii", "--border", "--no-border", "--color"]).command {
+            Some(Command::Play(args)) => {
+                let settings = TextSettings { ascii: true, color: false, border: true };
+                let text = args.text.settings(&settings);
+                assert_eq!(text, TextSettings { ascii: false, color: true, border: false });
+            },
+            _ => panic!("expected play"),
+        }
+        match Cli::parse_from(["snek", "play", "--ascii"]).command {
+            Some(Command::Play(args)) => {
+                let text = args.text.settings(&TextSettings::default());
+                assert_eq!(text, TextSettings { ascii: true, ..TextSettings::default() });
+            },
+            _ => panic!("expected play"),
+        }
+        assert!(Cli::try_parse_from(["snek", "play", "--players", "5"]).is_err());
+        assert!(Cli::try_parse_from(["snek", "play", "--map", "arena.txt", "--width", "9"]).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+use crate::rules::GameRules;
+use crate::snek::{BerryKind, SnekDirection, SnekPosition};
+
+/* An arena loaded from a plain text file.  Each character is one cell,
+ * using the same glyphs the game's Display output does, plus '#' for
+ * walls:
+ *
+ *     ################
+ *     #______________#
+ *     #__→_____▄_____#
+ *     #______##______#
+ *     #___█__##______#
+ *     #______________#
+ *     ################
+ *
+ *     _          empty
+ *     #          wall
+ *     █          rock
+ *     ▄ ● ○ » ◇  normal, golden, shrink, speed and rock-clearing berries
+ *     ↑ → ↓ ←    a Snek's head and the way it's facing (or ^ > v <)
+ *
+ * Every row must be the same width, there must be at least one head,
+ * and there must be at least one normal berry.  Maps with more than one
+ * head are for multiplayer; players are numbered in reading order.
+ * Trailing blank lines are ignored.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnekMap {
+    pub xsize: usize,
+    pub ysize: usize,
+    pub walls: Vec<SnekPosition>,
+    pub rocks: Vec<SnekPosition>,
+    pub berries: Vec<(SnekPosition, BerryKind)>,
+    pub starts: Vec<(SnekPosition, SnekDirection)>,
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    Io(std::io::Error),
+    /* Something wrong at a particular spot in the file.  Lines and
+     * columns count from 1, columns in characters.
+     */
+    Syntax { line: usize, column: usize, message: String },
+    /* Something wrong with the map as a whole. */
+    Invalid(String),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::Io(e) => write!(f, "map i/o error: {}", e),
+            MapError::Syntax { line, column, message } => {
+                write!(f, "line {}, column {}: {}", line, column, message)
+            },
+            MapError::Invalid(message) => write!(f, "invalid map: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+impl From<std::io::Error> for MapError {
+    fn from(e: std::io::Error) -> MapError {
+        MapError::Io(e)
+    }
+}
+
+enum Glyph {
+    Empty,
+    Wall,
+    Rock,
+    Berry(BerryKind),
+    Head(SnekDirection),
+}
+
+fn parse_glyph(c: char) -> Option<Glyph> {
+    match c {
+        '_' => Some(Glyph::Empty),
+        '#' => Some(Glyph::Wall),
+        '█' => Some(Glyph::Rock),
+        '▄' => Some(Glyph::Berry(BerryKind::Normal)),
+        '●' => Some(Glyph::Berry(BerryKind::Golden)),
+        '○' => Some(Glyph::Berry(BerryKind::Shrink)),
+        '»' => Some(Glyph::Berry(BerryKind::Speed)),
+        '◇' => Some(Glyph::Berry(BerryKind::RockClear)),
+        '↑' | '^' => Some(Glyph::Head(SnekDirection::North)),
+        '→' | '>' => Some(Glyph::Head(SnekDirection::East)),
+        '↓' | 'v' => Some(Glyph::Head(SnekDirection::South)),
+        '←' | '<' => Some(Glyph::Head(SnekDirection::West)),
+        _ => None,
+    }
+}
+
+impl SnekMap {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SnekMap, MapError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /* The given rules, resized to this map's board and with its Sneks'
+     * starting positions.
+     */
+    pub fn fit_rules(&self, rules: GameRules) -> GameRules {
+        let mut rules = rules.board_size(self.xsize, self.ysize);
+        rules.starts = self.starts.clone();
+        rules
+    }
+
+    /* Check that everything on the map is inside its board.  The parser
+     * guarantees this, but a map read back from a replay or a save file
+     * hasn't been through it.
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        let on_board = |pos: &SnekPosition| {
+            pos.x >= 0 && pos.y >= 0
+                && (pos.x as usize) < self.xsize && (pos.y as usize) < self.ysize
+        };
+        let positions = self.walls.iter()
+            .chain(&self.rocks)
+            .chain(self.berries.iter().map(|(pos, _)| pos))
+            .chain(self.starts.iter().map(|(pos, _)| pos));
+        for pos in positions {
+            if !on_board(pos) {
+                return Err(format!(
+                    "({}, {}) is off the {}x{} map", pos.x, pos.y, self.xsize, self.ysize
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SnekMap {
+    type Err = MapError;
+
+    fn from_str(s: &str) -> Result<SnekMap, MapError> {
+        let rows: Vec<&str> = s.trim_end_matches(['\n', '\r']).lines().collect();
+        if rows.is_empty() || rows[0].is_empty() {
+            return Err(MapError::Invalid("map is empty".to_string()));
+        }
+
+        let xsize = rows[0].chars().count();
+        let mut walls = Vec::new();
+        let mut rocks = Vec::new();
+        let mut berries = Vec::new();
+        let mut starts = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            let row = row.trim_end_matches('\r');
+            let width = row.chars().count();
+            if width != xsize {
+                return Err(MapError::Syntax {
+                    line: y + 1,
+                    column: width.min(xsize) + 1,
+                    message: format!("row is {} cells wide, expected {}", width, xsize),
+                });
+            }
+            for (x, c) in row.chars().enumerate() {
+                let pos = SnekPosition { x: x as i32, y: y as i32 };
+                let syntax_error = |message: String| MapError::Syntax {
+                    line: y + 1,
+                    column: x + 1,
+                    message,
+                };
+                match parse_glyph(c) {
+                    Some(Glyph::Empty) => {},
+                    Some(Glyph::Wall) => walls.push(pos),
+                    Some(Glyph::Rock) => rocks.push(pos),
+                    Some(Glyph::Berry(kind)) => berries.push((pos, kind)),
+                    Some(Glyph::Head(dir)) => starts.push((pos, dir)),
+                    None => return Err(syntax_error(format!("unknown glyph '{}'", c))),
+                }
+            }
+        }
+
+        if starts.is_empty() {
+            return Err(MapError::Invalid("no Snek head on the map".to_string()));
+        }
+        if !berries.iter().any(|(_, kind)| *kind == BerryKind::Normal) {
+            return Err(MapError::Invalid("no Snekberry on the map".to_string()));
+        }
+
+        Ok(SnekMap {
+            xsize,
+            ysize: rows.len(),
+            walls,
+            rocks,
+            berries,
+            starts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_map() {
+        let map: SnekMap = "#####\n#→_▄#\n#█_●#\n#####\n\n".parse().unwrap();
+        assert_eq!((map.xsize, map.ysize), (5, 4));
+        assert_eq!(map.walls.len(), 14);
+        assert_eq!(map.rocks, vec![SnekPosition { x: 1, y: 2 }]);
+        assert_eq!(map.berries, vec![
+            (SnekPosition { x: 3, y: 1 }, BerryKind::Normal),
+            (SnekPosition { x: 3, y: 2 }, BerryKind::Golden),
+        ]);
+        assert_eq!(map.starts, vec![(SnekPosition { x: 1, y: 1 }, SnekDirection::East)]);
+
+        let map: SnekMap = "_>_▄\n_<__\n".parse().unwrap();
+        assert_eq!(map.starts, vec![
+            (SnekPosition { x: 1, y: 0 }, SnekDirection::East),
+            (SnekPosition { x: 1, y: 1 }, SnekDirection::West),
+        ]);
+    }
+
+    #[test]
+    fn test_map_errors() {
+        let err = |s: &str| match s.parse::<SnekMap>() {
+            Err(MapError::Syntax { line, column, .. }) => Some((line, column)),
+            _ => None,
+        };
+        assert_eq!(err("____\n_>▄_\n__x_\n"), Some((3, 3)));
+        assert_eq!(err("____\n_>▄\n____\n"), Some((2, 4)));
+        assert!(matches!("____\n__▄_\n".parse::<SnekMap>(), Err(MapError::Invalid(_))));
+        assert!(matches!("____\n__>_\n".parse::<SnekMap>(), Err(MapError::Invalid(_))));
+    }
+}
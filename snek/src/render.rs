@@ -0,0 +1,41 @@
+use crate::snek::{GameState, SnekGame};
+
+/* Anything that can put a SnekGame in front of a player.  The game
+ * itself knows nothing about pixels or terminals; a Renderer pulls
+ * whatever it needs out of the game through its read-only accessors.
+ */
+pub trait Renderer {
+    /* Draw one complete frame of the game. */
+    fn draw(&mut self, game: &SnekGame) -> Result<(), String>;
+}
+
+/* The score line, or one per player, preceded by how the game ended
+ * once it has.  Shared by the frontends that print text.
+ */
+pub fn status_lines(game: &SnekGame) -> Vec<String> {
+    let mut lines = Vec::new();
+    match game.state() {
+        GameState::Dead(cause) => lines.push(format!("Game Over! {}.", cause)),
+        GameState::Won if game.sneks().len() > 1 => {
+            let best = game.sneks().iter().max_by_key(|snek| snek.points()).unwrap();
+            lines.push(format!("Player {} wins!", best.id() + 1));
+        },
+        GameState::Won => lines.push("Snek wins!".to_string()),
+        GameState::Running => {},
+    }
+    if game.sneks().len() == 1 {
+        lines.push(format!("Points: {}  Length: {}  Ticks: {}  Time: {}s",
+            game.points(), game.length(), game.ticks(), game.elapsed().as_secs()));
+    } else {
+        for snek in game.sneks() {
+            let status = match snek.death() {
+                Some(cause) => format!("  ({})", cause),
+                None => String::new(),
+            };
+            lines.push(format!("Player {}: Points: {}  Length: {}{}",
+                snek.id() + 1, snek.points(), snek.length(), status));
+        }
+        lines.push(format!("Ticks: {}  Time: {}s", game.ticks(), game.elapsed().as_secs()));
+    }
+    lines
+}
@@ -0,0 +1,243 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::map::SnekMap;
+use crate::rules::GameRules;
+use crate::snek::{SnekDirection, SnekEvent, SnekGame, SnekId};
+
+/* Bump this whenever the on-disk layout of a Replay changes, or when
+ * the same seed and inputs would play out differently, such as a
+ * change to where things spawn.
+ */
+pub const REPLAY_VERSION: u32 = 6;
+
+/* Everything needed to play a game back exactly: the seed and rules
+ * it started from, plus the turns each player asked for before each
+ * tick.  Since SnekGame is fully deterministic given those, replaying
+ * the inputs reproduces every berry, rock and death.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub rules: GameRules,
+    /* The arena the game was played on, if it came from a map file. */
+    #[serde(default)]
+    pub map: Option<SnekMap>,
+    /* ticks[n] holds the turns requested between step n-1 and step n,
+     * along with which Snek they were for.
+     */
+    pub ticks: Vec<Vec<(SnekId, SnekDirection)>>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    Version(u32),
+    /* The file parsed, but doesn't describe a game that can be played. */
+    Invalid(String),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "replay i/o error: {}", e),
+            ReplayError::Format(e) => write!(f, "malformed replay: {}", e),
+            ReplayError::Version(v) => write!(
+                f, "unsupported replay version {} (expected {})", v, REPLAY_VERSION
+            ),
+            ReplayError::Invalid(message) => write!(f, "invalid replay: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(e: std::io::Error) -> ReplayError {
+        ReplayError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ReplayError {
+    fn from(e: serde_json::Error) -> ReplayError {
+        ReplayError::Format(e)
+    }
+}
+
+impl Replay {
+    /* Create a fresh game in the state this replay starts from. */
+    pub fn new_game(&self) -> SnekGame {
+        match &self.map {
+            Some(map) => SnekGame::with_map(map, self.rules.clone(), self.seed),
+            None => SnekGame::with_rules(self.rules.clone(), self.seed),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ReplayError> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Replay, ReplayError> {
+        let reader = BufReader::new(File::open(path)?);
+        let replay: Replay = serde_json::from_reader(reader)?;
+        if replay.version != REPLAY_VERSION {
+            return Err(ReplayError::Version(replay.version));
+        }
+        let rules = match &replay.map {
+            Some(map) => {
+                map.validate().map_err(ReplayError::Invalid)?;
+                map.fit_rules(replay.rules.clone())
+            },
+            None => replay.rules.clone(),
+        };
+        rules.validate().map_err(ReplayError::Invalid)?;
+        Ok(replay)
+    }
+}
+
+/* Records a game as it's played.  Route the player's turns and the
+ * game ticks through the recorder instead of calling the game directly,
+ * starting from a freshly created game.
+ */
+#[derive(Debug)]
+pub struct ReplayRecorder {
+    replay: Replay,
+    pending: Vec<(SnekId, SnekDirection)>,
+}
+
+impl ReplayRecorder {
+    pub fn new(game: &SnekGame) -> ReplayRecorder {
+        ReplayRecorder {
+            replay: Replay {
+                version: REPLAY_VERSION,
+                seed: game.seed(),
+                rules: game.rules().clone(),
+                map: game.map().cloned(),
+                ticks: Vec::new(),
+            },
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn set_snekdir(&mut self, game: &mut SnekGame, dir: SnekDirection) {
+        self.set_snek_dir(game, 0, dir);
+    }
+
+    pub fn set_snek_dir(&mut self, game: &mut SnekGame, id: SnekId, dir: SnekDirection) {
+        self.pending.push((id, dir));
+        game.set_snek_dir(id, dir);
+    }
+
+    pub fn step(&mut self, game: &mut SnekGame) -> Vec<SnekEvent> {
+        self.replay.ticks.push(std::mem::take(&mut self.pending));
+        game.step()
+    }
+
+    /* The replay so far.  Turns made since the last step aren't part
+     * of it, as they never affected the game.
+     */
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+}
+
+/* Drives a game through the inputs stored in a replay, one tick per
+ * step().
+ */
+#[derive(Debug)]
+pub struct ReplayPlayer {
+    replay: Replay,
+    tick: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> ReplayPlayer {
+        ReplayPlayer { replay, tick: 0 }
+    }
+
+    pub fn new_game(&self) -> SnekGame {
+        self.replay.new_game()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tick >= self.replay.ticks.len()
+    }
+
+    /* Feed the next tick's inputs to the game and step it.  Returns
+     * None, leaving the game untouched, once the replay has run out.
+     */
+    pub fn step(&mut self, game: &mut SnekGame) -> Option<Vec<SnekEvent>> {
+        if self.is_finished() {
+            return None;
+        }
+        for (id, dir) in &self.replay.ticks[self.tick] {
+            game.set_snek_dir(*id, *dir);
+        }
+        self.tick += 1;
+        Some(game.step())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snek::SnekPosition;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_replay_matches_recording() {
+        let mut game = SnekGame::with_seed(32, 24, 99);
+        let mut recorder = ReplayRecorder::new(&game);
+        let mut frames = Vec::new();
+        let turns = [
+            (4, SnekDirection::South),
+            (8, SnekDirection::West),
+            (8, SnekDirection::North),
+            (14, SnekDirection::East),
+        ];
+        for tick in 0..40 {
+            for (_, dir) in turns.iter().filter(|(t, _)| *t == tick) {
+                recorder.set_snekdir(&mut game, *dir);
+            }
+            recorder.step(&mut game);
+            frames.push(format!("{}", game));
+        }
+
+        let file = NamedTempFile::new().unwrap();
+        recorder.replay().save(file.path()).unwrap();
+        let replay = Replay::load(file.path()).unwrap();
+        assert_eq!(&replay, recorder.replay());
+
+        let mut player = ReplayPlayer::new(replay);
+        let mut game = player.new_game();
+        for frame in frames {
+            assert!(player.step(&mut game).is_some());
+            assert_eq!(format!("{}", game), frame);
+        }
+        assert!(player.is_finished());
+        assert!(player.step(&mut game).is_none());
+    }
+
+    #[test]
+    fn test_load_invalid_replay() {
+        let game = SnekGame::with_seed(8, 8, 1);
+        let mut replay = ReplayRecorder::new(&game).replay().clone();
+        replay.rules = replay.rules.board_size(0, 8);
+        let file = NamedTempFile::new().unwrap();
+        replay.save(file.path()).unwrap();
+        assert!(matches!(Replay::load(file.path()), Err(ReplayError::Invalid(_))));
+
+        let mut map: SnekMap = "_>▄_\n".parse().unwrap();
+        map.walls.push(SnekPosition { x: 4, y: 0 });
+        replay.map = Some(map);
+        replay.save(file.path()).unwrap();
+        assert!(matches!(Replay::load(file.path()), Err(ReplayError::Invalid(_))));
+    }
+}
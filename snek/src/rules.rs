@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+
+use crate::snek::{BerryKind, SnekDirection, SnekPosition};
+
+/* The knobs that make one game of Snek different from another.  The
+ * defaults are the classic rules from the README; variants start from
+ * GameRules::new() and override what they need:
+ *
+ *     let rules = GameRules::new()
+ *         .board_size(16, 16)
+ *         .growth_per_berry(2)
+ *         .rocks_per_berry(0);
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameRules {
+    pub xsize: usize,
+    pub ysize: usize,
+    /* Segments Snek grows out behind its head over the first few ticks. */
+    pub starting_length: usize,
+    pub growth_per_berry: usize,
+    pub rocks_per_berry: usize,
+    pub points_per_berry: u64,
+    /* Berries and rocks scattered at random when the game starts. */
+    pub initial_berries: usize,
+    pub initial_rocks: usize,
+    /* Where each Snek's head starts and which way it's facing.  One
+     * entry per player; the first is player one.
+     */
+    pub starts: Vec<(SnekPosition, SnekDirection)>,
+    /* Leaving the board on one edge brings Snek back in on the opposite
+     * edge instead of into a wall.
+     */
+    pub wrap: bool,
+    /* Optional targets that win the game early, as well as filling the
+     * board.
+     */
+    pub win_length: Option<usize>,
+    pub win_points: Option<u64>,
+
+    /* Percent chance that eating a normal berry also spawns a bonus
+     * berry, and the relative odds of each kind turning up.
+     */
+    pub bonus_berry_chance: u32,
+    pub bonus_berry_weights: Vec<(BerryKind, u32)>,
+    pub golden_berry_points: u64,
+    /* Ticks a golden berry stays on the board before rotting away. */
+    pub golden_berry_ticks: u64,
+    pub shrink_berry_segments: usize,
+    pub speed_berry_ticks: u64,
+    /* Tick rate while a speed berry is in effect, as a percentage. */
+    pub speed_berry_percent: u32,
+}
+
+impl Default for GameRules {
+    fn default() -> GameRules {
+        GameRules {
+            xsize: 32,
+            ysize: 24,
+            starting_length: 3,
+            growth_per_berry: 1,
+            rocks_per_berry: 1,
+            points_per_berry: 10,
+            initial_berries: 1,
+            initial_rocks: 1,
+            starts: vec![(SnekPosition { x: 10, y: 10 }, SnekDirection::East)],
+            wrap: false,
+            win_length: None,
+            win_points: None,
+            bonus_berry_chance: 20,
+            bonus_berry_weights: vec![
+                (BerryKind::Golden, 4),
+                (BerryKind::Shrink, 2),
+                (BerryKind::Speed, 2),
+                (BerryKind::RockClear, 1),
+            ],
+            golden_berry_points: 50,
+            golden_berry_ticks: 30,
+            shrink_berry_segments: 3,
+            speed_berry_ticks: 50,
+            speed_berry_percent: 150,
+        }
+    }
+}
+
+impl GameRules {
+    pub fn new() -> GameRules {
+        GameRules::default()
+    }
+
+    pub fn board_size(mut self, xsize: usize, ysize: usize) -> GameRules {
+        self.xsize = xsize;
+        self.ysize = ysize;
+        self
+    }
+
+    pub fn starting_length(mut self, segments: usize) -> GameRules {
+        self.starting_length = segments;
+        self
+    }
+
+    pub fn growth_per_berry(mut self, segments: usize) -> GameRules {
+        self.growth_per_berry = segments;
+        self
+    }
+
+    pub fn rocks_per_berry(mut self, rocks: usize) -> GameRules {
+        self.rocks_per_berry = rocks;
+        self
+    }
+
+    pub fn points_per_berry(mut self, points: u64) -> GameRules {
+        self.points_per_berry = points;
+        self
+    }
+
+    pub fn initial_berries(mut self, berries: usize) -> GameRules {
+        self.initial_berries = berries;
+        self
+    }
+
+    pub fn initial_rocks(mut self, rocks: usize) -> GameRules {
+        self.initial_rocks = rocks;
+        self
+    }
+
+    /* Where the first Snek's head starts and which way it's facing. */
+    pub fn start(mut self, pos: SnekPosition, dir: SnekDirection) -> GameRules {
+        match self.starts.first_mut() {
+            Some(start) => *start = (pos, dir),
+            None => self.starts.push((pos, dir)),
+        }
+        self
+    }
+
+    /* Add another Snek to the game, starting at pos. */
+    pub fn add_snek(mut self, pos: SnekPosition, dir: SnekDirection) -> GameRules {
+        self.starts.push((pos, dir));
+        self
+    }
+
+    /* Line up the given number of Sneks down the left side of the
+     * board, all facing east and spaced evenly apart.  Uses the board
+     * size, so set that first.
+     */
+    pub fn players(mut self, count: usize) -> GameRules {
+        let count = count.max(1);
+        let x = (self.xsize / 3) as i32;
+        self.starts = (0..count)
+            .map(|i| {
+                let y = ((i + 1) * self.ysize / (count + 1)) as i32;
+                (SnekPosition { x, y }, SnekDirection::East)
+            })
+            .collect();
+        self
+    }
+
+    pub fn wrap(mut self, wrap: bool) -> GameRules {
+        self.wrap = wrap;
+        self
+    }
+
+    /* Win once Snek is this long, counting the head. */
+    pub fn win_length(mut self, length: usize) -> GameRules {
+        self.win_length = Some(length);
+        self
+    }
+
+    pub fn win_points(mut self, points: u64) -> GameRules {
+        self.win_points = Some(points);
+        self
+    }
+
+    pub fn bonus_berry_chance(mut self, percent: u32) -> GameRules {
+        self.bonus_berry_chance = percent;
+        self
+    }
+
+    /* Set how likely one kind of bonus berry is relative to the others.
+     * A weight of zero stops that kind from spawning.
+     */
+    pub fn bonus_berry_weight(mut self, kind: BerryKind, weight: u32) -> GameRules {
+        self.bonus_berry_weights.retain(|(k, _)| *k != kind);
+        self.bonus_berry_weights.push((kind, weight));
+        self
+    }
+
+    pub fn golden_berry_points(mut self, points: u64) -> GameRules {
+        self.golden_berry_points = points;
+        self
+    }
+
+    pub fn golden_berry_ticks(mut self, ticks: u64) -> GameRules {
+        self.golden_berry_ticks = ticks;
+        self
+    }
+
+    pub fn shrink_berry_segments(mut self, segments: usize) -> GameRules {
+        self.shrink_berry_segments = segments;
+        self
+    }
+
+    pub fn speed_berry(mut self, ticks: u64, percent: u32) -> GameRules {
+        self.speed_berry_ticks = ticks;
+        self.speed_berry_percent = percent;
+        self
+    }
+
+    /* Check the rules describe a game that can be played: a board with
+     * at least two cells, and at least one Snek, each starting on its own square
+     * of the board.  Rules from a file or the command line should pass
+     * this before a game is started with them.
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        let cells = self.xsize.checked_mul(self.ysize).unwrap_or(0);
+        if cells < 2 || self.xsize > i32::MAX as usize || self.ysize > i32::MAX as usize {
+            return Err(format!("board is {}x{}, it needs at least two cells", self.xsize, self.ysize));
+        }
+        if self.starts.is_empty() {
+            return Err("there must be at least one Snek".to_string());
+        }
+        for (i, (pos, _)) in self.starts.iter().enumerate() {
+            let on_board = (0..self.xsize as i32).contains(&pos.x) && (0..self.ysize as i32).contains(&pos.y);
+            if !on_board {
+                return Err(format!("player {} starts at ({}, {}), off the {}x{} board",
+                    i + 1, pos.x, pos.y, self.xsize, self.ysize));
+            }
+            if self.starts[..i].iter().any(|(other, _)| other == pos) {
+                return Err(format!("player {} starts on top of another Snek at ({}, {})", i + 1, pos.x, pos.y));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let pos = |x, y| SnekPosition { x, y };
+        let corner = || GameRules::new().start(pos(0, 0), SnekDirection::East);
+        assert!(GameRules::new().validate().is_ok());
+        assert!(corner().board_size(3, 1).validate().is_ok());
+        assert!(corner().board_size(1, 2).validate().is_ok());
+        assert!(corner().board_size(1, 1).validate().is_err());
+        assert!(corner().board_size(8, 0).wrap(true).validate().is_err());
+        assert!(corner().board_size(usize::MAX, 2).validate().is_err());
+        assert!(GameRules::new().board_size(8, 8).validate().is_err());
+        assert!(GameRules::new().start(pos(-1, 3), SnekDirection::East).validate().is_err());
+        assert!(GameRules::new().add_snek(pos(10, 10), SnekDirection::West).validate().is_err());
+
+        let mut rules = GameRules::new();
+        rules.starts.clear();
+        assert!(rules.validate().is_err());
+        let rules = rules.start(pos(1, 1), SnekDirection::North).board_size(2, 2);
+        assert!(rules.validate().is_ok());
+        // Four players don't fit down the side of a tiny board.
+        assert!(rules.players(4).validate().is_err());
+    }
+}
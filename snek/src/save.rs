@@ -0,0 +1,174 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::snek::SnekGame;
+
+/* Bump this whenever the on-disk layout of a saved SnekGame changes.
+ * Unlike a Replay, a save holds the whole board and RNG state, so a
+ * change to spawning alone doesn't need a bump.
+ */
+pub const SAVE_VERSION: u32 = 1;
+
+/* A game frozen mid-play: the board, every Snek along with the turns
+ * it has queued up, and the RNG, so a loaded game carries on exactly
+ * as the saved one would have.
+ */
+#[derive(Serialize)]
+struct SaveFile<'a> {
+    version: u32,
+    game: &'a SnekGame,
+}
+
+/* Read the version before the game, so an old save is reported as
+ * such rather than as whichever field it trips over first.
+ */
+#[derive(Deserialize)]
+struct LoadFile {
+    version: u32,
+    game: serde_json::Value,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    Version(u32),
+    /* The file parsed, but the game in it doesn't hang together. */
+    Invalid(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "save i/o error: {}", e),
+            SaveError::Format(e) => write!(f, "malformed save: {}", e),
+            SaveError::Version(v) => write!(
+                f, "unsupported save version {} (expected {})", v, SAVE_VERSION
+            ),
+            SaveError::Invalid(message) => write!(f, "invalid save: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(e: std::io::Error) -> SaveError {
+        SaveError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> SaveError {
+        SaveError::Format(e)
+    }
+}
+
+/* Write game to path, creating its directory if need be. */
+pub fn save<P: AsRef<Path>>(game: &SnekGame, path: P) -> Result<(), SaveError> {
+    if let Some(dir) = path.as_ref().parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(writer, &SaveFile { version: SAVE_VERSION, game })?;
+    Ok(())
+}
+
+/* Read back a game written by save().  It runs on a fresh SystemClock;
+ * swap in another with set_clock() if need be.
+ */
+pub fn load<P: AsRef<Path>>(path: P) -> Result<SnekGame, SaveError> {
+    let reader = BufReader::new(File::open(path)?);
+    let file: LoadFile = serde_json::from_reader(reader)?;
+    if file.version != SAVE_VERSION {
+        return Err(SaveError::Version(file.version));
+    }
+    let game: SnekGame = serde_json::from_value(file.game)?;
+    game.validate().map_err(SaveError::Invalid)?;
+    Ok(game)
+}
+
+fn save_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("snek"))
+}
+
+/* Where the frontend keeps a game that was quit part way through. */
+pub fn autosave_path() -> Option<PathBuf> {
+    save_dir().map(|dir| dir.join("autosave.json"))
+}
+
+/* The single slot behind the quicksave and quickload keys. */
+pub fn quicksave_path() -> Option<PathBuf> {
+    save_dir().map(|dir| dir.join("quicksave.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use crate::clock::ManualClock;
+    use crate::rules::GameRules;
+    use crate::snek::SnekDirection;
+
+    #[test]
+    fn test_save_and_resume() {
+        let rules = GameRules::new().board_size(16, 12).players(2);
+        let mut game = SnekGame::with_rules(rules, 7);
+        game.set_clock(Box::new(ManualClock::new()));
+        for _ in 0..5 {
+            game.step();
+        }
+        // Leave a turn queued up, which should survive the save.
+        game.set_snek_dir(1, SnekDirection::North);
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("nested").join("save.json");
+        save(&game, &path).unwrap();
+        let mut loaded = load(&path).unwrap();
+        loaded.set_clock(Box::new(ManualClock::new()));
+        assert_eq!(format!("{}", loaded), format!("{}", game));
+
+        let turns = [SnekDirection::South, SnekDirection::West, SnekDirection::North];
+        for tick in 0..30 {
+            let dir = turns[tick / 10];
+            game.set_snekdir(dir);
+            loaded.set_snekdir(dir);
+            assert_eq!(loaded.step(), game.step());
+            assert_eq!(format!("{}", loaded), format!("{}", game));
+        }
+        assert_eq!(loaded.points(), game.points());
+    }
+
+    #[test]
+    fn test_save_version() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("save.json");
+        fs::write(&path, r#"{"version": 0, "game": null}"#).unwrap();
+        assert!(matches!(load(&path), Err(SaveError::Version(0))));
+    }
+
+    #[test]
+    fn test_invalid_save() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("save.json");
+        save(&SnekGame::with_rules(GameRules::new().players(2), 3), &path).unwrap();
+        let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+
+        let damage = |edit: &dyn Fn(&mut serde_json::Value)| {
+            let mut file = saved.clone();
+            edit(&mut file["game"]);
+            fs::write(&path, file.to_string()).unwrap();
+            load(&path)
+        };
+        assert!(damage(&|_| {}).is_ok());
+        assert!(matches!(damage(&|game| game["sneks"][1]["head_pos"]["x"] = serde_json::json!(99)),
+            Err(SaveError::Invalid(_))));
+        assert!(matches!(damage(&|game| game["rules"]["xsize"] = serde_json::json!(0)),
+            Err(SaveError::Invalid(_))));
+        assert!(matches!(damage(&|game| { game["sneks"].as_array_mut().unwrap().pop(); }),
+            Err(SaveError::Invalid(_))));
+    }
+}
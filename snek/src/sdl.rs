@@ -0,0 +1,245 @@
+#![allow(dead_code)]
+use sdl2::pixels::{Color};
+use sdl2::rect::Point;
+use sdl2::render::{Canvas};
+use sdl2::rect::Rect;
+// use sdl2::surface::Surface;
+use sdl2::video::{Window};
+
+use crate::render::Renderer;
+use crate::settings::{Palette, Rgb};
+use crate::snek::{BerryKind, GameState, SnekGame, SnekObject, SnekPosition};
+
+fn rgb((r, g, b): Rgb) -> Color {
+    Color::RGB(r, g, b)
+}
+
+/* Draws the game into an SDL window. */
+pub struct SdlRenderer {
+    canvas: Canvas<Window>,
+    palette: Palette,
+}
+
+impl SdlRenderer {
+    pub fn new(canvas: Canvas<Window>) -> SdlRenderer {
+        SdlRenderer { canvas, palette: Palette::default() }
+    }
+
+    pub fn palette(mut self, palette: Palette) -> SdlRenderer {
+        self.palette = palette;
+        self
+    }
+
+    pub fn canvas(&mut self) -> &mut Canvas<Window> {
+        &mut self.canvas
+    }
+
+    // Need a translate snakepos on the board to xy on the canvas.
+    fn translate_snakepos_to_pos(&self) -> (usize, usize) {
+        (0, 0)
+    }
+
+    fn draw_board(&mut self, game: &SnekGame) {
+        let canvas = &mut self.canvas;
+        let border_color = rgb(self.palette.wall);
+        // Draw line around full canvas, 3-5 pixel thickness.  Rounded borders.
+        // Draw line at 3/4ths (2/3rds or other fraction?) vertically
+        // Left is gameboard, right is "game status"
+        // Add "Snek!" text to status
+        // Maybe animate it?
+        // It should be a TTF font, C64?
+        canvas.set_draw_color(border_color);
+        canvas.set_draw_color(
+            Color {
+                r: border_color.r / 2,
+                g: border_color.g / 2,
+                b: border_color.b / 2,
+                a: border_color.a,
+            }
+        );
+
+        // Outline the playfield.  Edges of a wrapping board lead back
+        // around rather than into a wall, so they're drawn dashed.
+        let orig_color = canvas.draw_color();
+        canvas.set_draw_color(border_color);
+        let w = game.xsize() as i32 * 32;
+        let h = game.ysize() as i32 * 32;
+        let corners = [
+            Point::new(0, 0),
+            Point::new(w - 1, 0),
+            Point::new(w - 1, h - 1),
+            Point::new(0, h - 1),
+            Point::new(0, 0),
+        ];
+        for edge in corners.windows(2) {
+            if game.rules().wrap {
+                draw_dashed_line(canvas, edge[0], edge[1]);
+            } else {
+                let _ = canvas.draw_line(edge[0], edge[1]);
+            }
+        }
+        canvas.set_draw_color(orig_color);
+    }
+
+    fn draw_head(&mut self, game: &SnekGame) {
+        const FACE_COLOR: Color = Color::RGB(255, 80, 80);
+        const FACE_WIDTH: u32 = 4;
+        let canvas = &mut self.canvas;
+        let orig_color = canvas.draw_color();
+        for snek in game.sneks() {
+            let head_color = rgb(self.palette.snek(snek.id()).head);
+            let pos = snek.head_pos();
+            let x = pos.x * 32;
+            let y = pos.y * 32;
+            canvas.set_draw_color(head_color);
+            let _ = canvas.fill_rect(Rect::new(x, y, 32, 32));
+        }
+        canvas.set_draw_color(FACE_COLOR);
+        // match game.snek_head_dir() {
+            // SnekDirection::North => { canvas.fill_rect(Rect::new(x, y, 32, FACE_WIDTH)); },
+            // SnekDirection::East => { canvas.fill_rect(Rect::new(x+32 - FACE_WIDTH, y, FACE_WIDTH, 32)); },
+            // SnekDirection::West => { canvas.fill_rect(Rect::new(x, y, 32, 2)); },
+            // SnekDirection::South => { canvas.fill_rect(Rect::new(x, y, 32, 1)); },
+        // }
+
+        canvas.set_draw_color(orig_color);
+    }
+
+    /* Fill every cell holding the given kind of object with a solid colour. */
+    fn draw_objects(&mut self, game: &SnekGame, kind: SnekObject, color: Color) {
+        let canvas = &mut self.canvas;
+        let orig_color = canvas.draw_color();
+        canvas.set_draw_color(color);
+        for (pos, _) in game.cells().filter(|(_, obj)| *obj == kind) {
+            let x = pos.x * 32;
+            let y = pos.y * 32;
+            let _ = canvas.fill_rect(Rect::new(x, y, 32, 32));
+        }
+        canvas.set_draw_color(orig_color);
+    }
+
+    fn draw_segments(&mut self, game: &SnekGame) {
+        for snek in game.sneks() {
+            let segment_color = rgb(self.palette.snek(snek.id()).body);
+            self.draw_objects(game, SnekObject::Segment(snek.id()), segment_color);
+        }
+    }
+
+    fn draw_berries(&mut self, game: &SnekGame) {
+        for kind in BerryKind::ALL {
+            let color = match kind {
+                BerryKind::Normal => self.palette.normal_berry,
+                BerryKind::Golden => self.palette.golden_berry,
+                BerryKind::Shrink => self.palette.shrink_berry,
+                BerryKind::Speed => self.palette.speed_berry,
+                BerryKind::RockClear => self.palette.rock_clear_berry,
+            };
+            self.draw_objects(game, SnekObject::Berry(kind), rgb(color));
+        }
+    }
+
+    fn draw_rocks(&mut self, game: &SnekGame) {
+        self.draw_objects(game, SnekObject::Rock, rgb(self.palette.rock));
+    }
+
+    /* Walls inside the board, from map files.  The perimeter is drawn
+     * by draw_board().
+     */
+    fn draw_walls(&mut self, game: &SnekGame) {
+        self.draw_objects(game, SnekObject::Wall, rgb(self.palette.wall));
+    }
+
+    /* Once the game's won, light up the empty cells in a checkerboard
+     * and put a thick gold frame round the board.
+     */
+    fn draw_win(&mut self, game: &SnekGame) {
+        const FRAME_WIDTH: u32 = 8;
+        let win_color = rgb(self.palette.win);
+        let canvas = &mut self.canvas;
+        let orig_color = canvas.draw_color();
+        canvas.set_draw_color(win_color);
+        let (xsize, ysize) = (game.xsize() as i32, game.ysize() as i32);
+        for y in 0..ysize {
+            for x in 0..xsize {
+                let pos = SnekPosition { x, y };
+                if (x + y) % 2 == 0 && game.get_cell(&pos) == SnekObject::Empty {
+                    let _ = canvas.fill_rect(Rect::new(x * 32 + 8, y * 32 + 8, 16, 16));
+                }
+            }
+        }
+        let (w, h) = (xsize as u32 * 32, ysize as u32 * 32);
+        let _ = canvas.fill_rects(&[
+            Rect::new(0, 0, w, FRAME_WIDTH),
+            Rect::new(0, (h - FRAME_WIDTH) as i32, w, FRAME_WIDTH),
+            Rect::new(0, 0, FRAME_WIDTH, h),
+            Rect::new((w - FRAME_WIDTH) as i32, 0, FRAME_WIDTH, h),
+        ]);
+        canvas.set_draw_color(orig_color);
+    }
+
+    /* Draw the game grid. */
+    pub fn draw_grid(&mut self) {
+        const SPACING: usize = 32;
+        const GRID_COLOR: Color = Color::RGB(0, 255, 0);
+        const WALL_COLOR: Color = Color::RGB(255, 0, 0);
+
+        let canvas = &mut self.canvas;
+        let orig_color = canvas.draw_color();
+
+        // Get surface dimensions, coerce into i32 so we can use them as Points.
+        let (xsize, ysize) = canvas.output_size().unwrap();
+        let xsize: i32 = xsize.try_into().unwrap();
+        let ysize: i32 = ysize.try_into().unwrap();
+
+        // Draw the grid
+        canvas.set_draw_color(GRID_COLOR);
+        for x in (0..xsize).step_by(SPACING) {
+            canvas.draw_line(Point::new(x, 0), Point::new(x, ysize)).unwrap();
+        }
+        for y in (0..ysize).step_by(SPACING) {
+            canvas.draw_line(Point::new(0, y), Point::new(xsize, y)).unwrap();
+        }
+
+        // Draw the walls
+        canvas.set_draw_color(WALL_COLOR);
+        canvas.draw_line(Point::new(0, 0), Point::new(xsize - 1, 0)).unwrap();
+        canvas.draw_line(Point::new(0, 0), Point::new(0, ysize - 1)).unwrap();
+        canvas.draw_line(Point::new(xsize - 1, 0), Point::new(xsize - 1, ysize - 1)).unwrap();
+        canvas.draw_line(Point::new(0, ysize - 1), Point::new(xsize - 1, ysize - 1)).unwrap();
+        
+        // Restore original color
+        canvas.set_draw_color(orig_color);
+    }
+}
+
+/* Draw a horizontal or vertical line as 8 pixel dashes. */
+fn draw_dashed_line(canvas: &mut Canvas<Window>, from: Point, to: Point) {
+    const DASH: i32 = 8;
+    let len = (to.x() - from.x()).abs().max((to.y() - from.y()).abs());
+    let dx = (to.x() - from.x()).signum();
+    let dy = (to.y() - from.y()).signum();
+    for start in (0..len).step_by(2 * DASH as usize) {
+        let end = (start + DASH).min(len);
+        let a = Point::new(from.x() + dx * start, from.y() + dy * start);
+        let b = Point::new(from.x() + dx * end, from.y() + dy * end);
+        let _ = canvas.draw_line(a, b);
+    }
+}
+
+impl Renderer for SdlRenderer {
+    fn draw(&mut self, game: &SnekGame) -> Result<(), String> {
+        self.canvas.clear();
+        self.draw_board(game);
+        self.draw_head(game);
+        self.draw_segments(game);
+        self.draw_walls(game);
+        self.draw_rocks(game);
+        self.draw_berries(game);
+        if game.state() == GameState::Won {
+            self.draw_win(game);
+        }
+        // self.draw_grid();
+        self.canvas.present();
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use sdl2::controller::Axis;
+use sdl2::event::Event;
+
+use crate::input::{Action, Gamepads, KeyBindings, PadBindings, StickAxis};
+use crate::snek::SnekId;
+
+/* Turns SDL events into Actions: key presses through the KeyBindings,
+ * game controller buttons and the left stick through Gamepads.  SDL
+ * only reports controllers it has been asked to open, so opening them
+ * as they're plugged in is up to the frontend, which then hands each
+ * one's instance id to add_pad().
+ */
+pub struct SdlInput {
+    keys: KeyBindings,
+    pads: Gamepads,
+}
+
+impl SdlInput {
+    pub fn new(keys: KeyBindings, pads: PadBindings) -> SdlInput {
+        SdlInput { keys, pads: Gamepads::new(pads) }
+    }
+
+    /* Give a newly opened controller the next free player. */
+    pub fn add_pad(&mut self, pad: u32) -> SnekId {
+        self.pads.player(pad)
+    }
+
+    pub fn event(&mut self, event: &Event) -> Option<Action> {
+        match event {
+            Event::Quit { .. } => Some(Action::Quit),
+            // Holding a key down mustn't flicker the pause on and off.
+            Event::KeyDown { keycode: Some(key), repeat: false, .. } => self.keys.action(&key.name()),
+            Event::ControllerButtonDown { which, button, .. } => self.pads.button(*which, &button.string()),
+            Event::ControllerAxisMotion { which, axis: Axis::LeftX, value, .. } => {
+                self.pads.stick(*which, StickAxis::X, *value)
+            },
+            Event::ControllerAxisMotion { which, axis: Axis::LeftY, value, .. } => {
+                self.pads.stick(*which, StickAxis::Y, *value)
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.pads.remove(*which);
+                None
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::controller::Button;
+    use sdl2::keyboard::{Keycode, Mod};
+    use crate::input::KeyLayout;
+    use crate::snek::SnekDirection;
+
+    fn key(keycode: Keycode, repeat: bool) -> Event {
+        Event::KeyDown { timestamp: 0, window_id: 0, keycode: Some(keycode), scancode: None, keymod: Mod::NOMOD, repeat }
+    }
+
+    fn button(which: u32, button: Button) -> Event {
+        Event::ControllerButtonDown { timestamp: 0, which, button }
+    }
+
+    fn axis(which: u32, axis: Axis, value: i16) -> Event {
+        Event::ControllerAxisMotion { timestamp: 0, which, axis, value }
+    }
+
+    #[test]
+    fn test_sdl_events() {
+        let mut input = SdlInput::new(KeyBindings::new(KeyLayout::Vim), PadBindings::default());
+        assert_eq!(input.event(&key(Keycode::H, false)), Some(Action::Turn(0, SnekDirection::West)));
+        assert_eq!(input.event(&key(Keycode::P, false)), Some(Action::Pause));
+        assert_eq!(input.event(&key(Keycode::P, true)), None);
+        assert_eq!(input.event(&Event::Quit { timestamp: 0 }), Some(Action::Quit));
+
+        // Controllers steer players in the order they were opened.
+        assert_eq!(input.add_pad(3), 0);
+        assert_eq!(input.event(&button(5, Button::DPadDown)), Some(Action::Turn(1, SnekDirection::South)));
+        assert_eq!(input.event(&button(3, Button::Back)), Some(Action::Restart));
+        assert_eq!(input.event(&axis(3, Axis::LeftY, -30_000)), Some(Action::Turn(0, SnekDirection::North)));
+        assert_eq!(input.event(&axis(3, Axis::RightX, 30_000)), None);
+
+        input.event(&Event::ControllerDeviceRemoved { timestamp: 0, which: 3 });
+        assert_eq!(input.add_pad(8), 0);
+    }
+}
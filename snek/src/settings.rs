@@ -0,0 +1,217 @@
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::input::{KeyBindings, PadBindings};
+use crate::theme::TextTheme;
+
+/* A red, green, blue colour, written [r, g, b] in the settings file. */
+pub type Rgb = (u8, u8, u8);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnekColors {
+    pub head: Rgb,
+    pub body: Rgb,
+}
+
+/* The colours the SDL frontend fills cells with.  Sneks take colours
+ * in turn, starting over if there are more players than colours.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Palette {
+    pub normal_berry: Rgb,
+    pub golden_berry: Rgb,
+    pub shrink_berry: Rgb,
+    pub speed_berry: Rgb,
+    pub rock_clear_berry: Rgb,
+    pub rock: Rgb,
+    /* Walls, and the edge of the board. */
+    pub wall: Rgb,
+    /* The checkerboard and frame drawn once the game's won. */
+    pub win: Rgb,
+    pub sneks: Vec<SnekColors>,
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette {
+            normal_berry: (255, 0, 0),
+            golden_berry: (255, 200, 0),
+            shrink_berry: (60, 60, 255),
+            speed_berry: (0, 255, 255),
+            rock_clear_berry: (255, 0, 255),
+            rock: (120, 120, 120),
+            wall: (0, 200, 0),
+            win: (255, 200, 0),
+            sneks: vec![
+                SnekColors { head: (0, 80, 80), body: (255, 255, 0) },
+                SnekColors { head: (0, 40, 160), body: (0, 200, 255) },
+                SnekColors { head: (120, 0, 80), body: (255, 80, 200) },
+                SnekColors { head: (80, 80, 80), body: (255, 255, 255) },
+            ],
+        }
+    }
+}
+
+impl Palette {
+    pub fn snek(&self, id: usize) -> SnekColors {
+        self.sneks.get(id % self.sneks.len().max(1)).copied()
+            .unwrap_or(SnekColors { head: (255, 255, 255), body: (255, 255, 255) })
+    }
+}
+
+/* How the terminal frontend draws the board; see TextTheme. */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextSettings {
+    pub ascii: bool,
+    pub color: bool,
+    pub border: bool,
+}
+
+impl Default for TextSettings {
+    fn default() -> TextSettings {
+        TextSettings { ascii: false, color: true, border: false }
+    }
+}
+
+impl TextSettings {
+    pub fn theme(&self) -> TextTheme {
+        let theme = if self.ascii { TextTheme::ascii() } else { TextTheme::default() };
+        // The ASCII theme has no colour to turn on.
+        theme.color(self.color && !self.ascii).border(self.border)
+    }
+}
+
+/* What the player has chosen to change from the built in defaults,
+ * kept between runs in settings.toml.  Anything left out of the file
+ * keeps its default, and options given on the command line win over
+ * both.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /* Ticks per second. */
+    pub fps: u32,
+    /* Board size in cells.  Each frontend has its own idea of a good
+     * size for whichever of these isn't set.
+     */
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    /* Initials to put on the high score table. */
+    pub initials: String,
+    /* The high score database, if not highscores::default_path(). */
+    pub highscore_db: Option<PathBuf>,
+    pub text: TextSettings,
+    pub colors: Palette,
+    pub keys: KeyBindings,
+    /* Game controller buttons, for the SDL frontend. */
+    pub pad: PadBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            fps: 10,
+            width: None,
+            height: None,
+            initials: "AAA".to_string(),
+            highscore_db: None,
+            text: TextSettings::default(),
+            colors: Palette::default(),
+            keys: KeyBindings::default(),
+            pad: PadBindings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /* The settings as they'd be written to settings.toml. */
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("settings always serialize")
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(std::io::Error),
+    Format(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Io(e) => write!(f, "settings i/o error: {}", e),
+            SettingsError::Format(e) => write!(f, "malformed settings: {}", e),
+            SettingsError::Invalid(e) => write!(f, "invalid settings: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl From<std::io::Error> for SettingsError {
+    fn from(e: std::io::Error) -> SettingsError {
+        SettingsError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for SettingsError {
+    fn from(e: toml::de::Error) -> SettingsError {
+        SettingsError::Format(e)
+    }
+}
+
+/* Where the frontends look for settings.toml. */
+pub fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("snek").join("settings.toml"))
+}
+
+/* Read the settings at path.  If there's no file there, that's the
+ * defaults.
+ */
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Settings, SettingsError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Settings::default()),
+        Err(e) => return Err(e.into()),
+    };
+    let settings: Settings = toml::from_str(&text)?;
+    if settings.fps == 0 {
+        return Err(SettingsError::Invalid("fps must be at least 1".to_string()));
+    }
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::KeyLayout;
+
+    #[test]
+    fn test_load_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        assert_eq!(load(&path).unwrap(), Settings::default());
+
+        fs::write(&path, "fps = 15\nwidth = 20\n\n[text]\nascii = true\n\n[keys]\nlayout = \"vim\"\npause = [\"Return\"]\n").unwrap();
+        let settings = load(&path).unwrap();
+        assert_eq!((settings.fps, settings.width, settings.height), (15, Some(20), None));
+        assert_eq!(settings.text.theme(), TextTheme::ascii());
+        assert_eq!(settings.keys.pause, vec!["Return".to_string()]);
+        // Whatever isn't rebound comes from the layout.
+        assert_eq!(settings.keys.players, KeyBindings::new(KeyLayout::Vim).players);
+        assert_eq!(settings.keys.quit, KeyBindings::default().quit);
+
+        // Whatever's written out reads back the same.
+        fs::write(&path, settings.to_toml()).unwrap();
+        assert_eq!(load(&path).unwrap(), settings);
+
+        fs::write(&path, "fps = \"fast\"\n").unwrap();
+        assert!(matches!(load(&path), Err(SettingsError::Format(_))));
+    }
+}
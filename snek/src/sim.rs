@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Instant;
+use serde::Serialize;
+
+use crate::controller::{collect_turns, BotKind, SnekController};
+use crate::map::SnekMap;
+use crate::rules::GameRules;
+use crate::snek::{GameState, SnekGame};
+
+/* A batch of headless games, all played by the same kind of bot.  Game
+ * n is started from seed first_seed + n, so a batch is reproducible
+ * and a game that turns up something odd can be replayed on its own.
+ */
+#[derive(Clone, Debug)]
+pub struct SimConfig {
+    pub rules: GameRules,
+    pub map: Option<SnekMap>,
+    pub bot: BotKind,
+    pub first_seed: u64,
+    pub games: u64,
+    /* Games still going after this many ticks are stopped and counted
+     * as timeouts, so a bot that never dies can't stall the batch.
+     */
+    pub max_ticks: u64,
+}
+
+impl Default for SimConfig {
+    fn default() -> SimConfig {
+        SimConfig {
+            rules: GameRules::new(),
+            map: None,
+            bot: BotKind::Path,
+            first_seed: 0,
+            games: 100,
+            max_ticks: 10_000,
+        }
+    }
+}
+
+/* Spread of a set of scores. */
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+    pub median: u64,
+}
+
+impl Summary {
+    fn of(values: &mut [u64]) -> Summary {
+        if values.is_empty() {
+            return Summary::default();
+        }
+        values.sort_unstable();
+        Summary {
+            min: values[0],
+            max: values[values.len() - 1],
+            mean: values.iter().sum::<u64>() as f64 / values.len() as f64,
+            median: values[values.len() / 2],
+        }
+    }
+}
+
+/* What came out of a batch.  Points, lengths and deaths are counted
+ * per Snek, so a two player game adds two of each.  Deaths are keyed
+ * by cause, plus "won" and "timeout" for Sneks that didn't die.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SimReport {
+    pub games: u64,
+    pub sneks: u64,
+    pub points: Summary,
+    pub deaths: BTreeMap<String, u64>,
+    pub mean_length: f64,
+    pub mean_ticks: f64,
+    pub ticks_per_sec: f64,
+}
+
+/* Play one game to the end, or to max_ticks. */
+pub fn play_game(config: &SimConfig, seed: u64) -> SnekGame {
+    let mut game = match &config.map {
+        Some(map) => SnekGame::on_map(map, config.rules.clone(), seed),
+        None => SnekGame::with_rules(config.rules.clone(), seed),
+    };
+    let mut bots: Vec<Box<dyn SnekController>> = game.sneks().iter().map(|_| config.bot.controller()).collect();
+    while !game.is_over() && game.ticks() < config.max_ticks {
+        let mut controllers: Vec<&mut dyn SnekController> = bots.iter_mut()
+            .map(|bot| bot.as_mut() as &mut dyn SnekController)
+            .collect();
+        for (id, dir) in collect_turns(&game, &mut controllers) {
+            game.set_snek_dir(id, dir);
+        }
+        game.step();
+    }
+    game
+}
+
+pub fn run(config: &SimConfig) -> SimReport {
+    let start = Instant::now();
+    let mut points = Vec::new();
+    let mut lengths = 0;
+    let mut ticks = 0;
+    let mut deaths = BTreeMap::new();
+    for seed in config.first_seed..config.first_seed + config.games {
+        let game = play_game(config, seed);
+        ticks += game.ticks();
+        for snek in game.sneks() {
+            points.push(snek.points());
+            lengths += snek.length() as u64;
+            let outcome = match (snek.death(), game.state()) {
+                (Some(cause), _) => format!("{:?}", cause),
+                (None, GameState::Won) => "won".to_string(),
+                (None, _) => "timeout".to_string(),
+            };
+            *deaths.entry(outcome).or_insert(0) += 1;
+        }
+    }
+
+    let secs = start.elapsed().as_secs_f64();
+    let sneks = points.len() as u64;
+    SimReport {
+        games: config.games,
+        sneks,
+        points: Summary::of(&mut points),
+        deaths,
+        mean_length: lengths as f64 / sneks.max(1) as f64,
+        mean_ticks: ticks as f64 / config.games.max(1) as f64,
+        ticks_per_sec: if secs > 0.0 { ticks as f64 / secs } else { 0.0 },
+    }
+}
+
+impl fmt::Display for SimReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "games          {:>10}", self.games)?;
+        writeln!(f, "sneks          {:>10}", self.sneks)?;
+        writeln!(f, "points min     {:>10}", self.points.min)?;
+        writeln!(f, "points median  {:>10}", self.points.median)?;
+        writeln!(f, "points mean    {:>10.1}", self.points.mean)?;
+        writeln!(f, "points max     {:>10}", self.points.max)?;
+        writeln!(f, "mean length    {:>10.1}", self.mean_length)?;
+        writeln!(f, "mean ticks     {:>10.1}", self.mean_ticks)?;
+        writeln!(f, "ticks/sec      {:>10.0}", self.ticks_per_sec)?;
+        writeln!(f, "deaths")?;
+        for (cause, count) in &self.deaths {
+            writeln!(f, "  {:<13}{:>10}", cause, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snek::{SnekDirection, SnekPosition};
+
+    #[test]
+    fn test_sim_run() {
+        let config = SimConfig {
+            rules: GameRules::new()
+                .board_size(12, 12)
+                .start(SnekPosition { x: 3, y: 3 }, SnekDirection::East),
+            games: 4,
+            max_ticks: 200,
+            ..SimConfig::default()
+        };
+        let report = run(&config);
+        assert_eq!(report.games, 4);
+        assert_eq!(report.deaths.values().sum::<u64>(), 4);
+        assert!(report.points.min <= report.points.median && report.points.median <= report.points.max);
+
+        // Same seeds, same games.
+        let again = run(&config);
+        assert_eq!((again.points, again.deaths), (report.points, report.deaths));
+    }
+}
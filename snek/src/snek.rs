@@ -1,20 +1,23 @@
 #![allow(dead_code)]
-use std::collections::HashMap;
+use std::collections::{HashSet, VecDeque};
 use std::fmt;
 use std::hash::Hash;
-use rand::Rng;
-use colored::{Colorize};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 
-use sdl2::pixels::{Color};
-use sdl2::rect::Point;
-use sdl2::render::{Canvas};
-use sdl2::rect::Rect;
-// use sdl2::surface::Surface;
-use sdl2::video::{Window};
+use crate::board::Board;
+use crate::clock::{Clock, SystemClock};
+use crate::map::{MapError, SnekMap};
+use crate::rules::GameRules;
+use crate::theme::{Drawn, TextTheme, Themed};
 
 // TODO; have a struct for points in the game board.  Replace all x, y function calls.
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum SnekDirection {
     North,
     East,
@@ -22,86 +25,446 @@ pub enum SnekDirection {
     South,
 }
 
-#[derive(Clone, Debug, PartialEq)]
-enum SnekObject {
-    Berry,
+impl SnekDirection {
+    /* The direction that would have Snek turn back on itself. */
+    pub fn opposite(&self) -> SnekDirection {
+        match self {
+            SnekDirection::North => SnekDirection::South,
+            SnekDirection::East => SnekDirection::West,
+            SnekDirection::West => SnekDirection::East,
+            SnekDirection::South => SnekDirection::North,
+        }
+    }
+}
+
+/* The different Snekberries.  Normal berries are the ones the README
+ * rules are about: there's always exactly one, and eating it grows
+ * Snek and drops rocks.  The rest are bonus berries that sometimes
+ * turn up alongside it.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BerryKind {
+    Normal,
+    /* Worth bonus points, but rots away if not eaten quickly. */
+    Golden,
+    /* Takes segments off Snek's tail. */
+    Shrink,
+    /* Changes the game speed for a while. */
+    Speed,
+    /* Clears every rock off the board. */
+    RockClear,
+}
+
+impl BerryKind {
+    pub const ALL: [BerryKind; 5] = [
+        BerryKind::Normal,
+        BerryKind::Golden,
+        BerryKind::Shrink,
+        BerryKind::Speed,
+        BerryKind::RockClear,
+    ];
+}
+
+/* Identifies one Snek on a board shared by several.  Sneks are
+ * numbered from 0 in the order of the rules' start positions.
+ */
+pub type SnekId = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SnekObject {
+    Berry(BerryKind),
     Empty,
-    Head,
-    Segment,
+    Head(SnekId),
+    Segment(SnekId),
     Rock,
     Wall,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SnekPosition {
-    x: i32,
-    y: i32,
+    pub x: i32,
+    pub y: i32,
+}
+
+/* Why Snek died. */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeathCause {
+    Wall,
+    Rock,
+    SelfCollision,
+    /* Ran into some other Snek's body. */
+    OtherSnek,
+    /* Two heads arrived at the same cell, or tried to pass through
+     * each other.  Both Sneks die.
+     */
+    HeadOn,
+}
+
+impl fmt::Display for DeathCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeathCause::Wall => write!(f, "Snek hit a wall"),
+            DeathCause::Rock => write!(f, "Snek hit a rock"),
+            DeathCause::SelfCollision => write!(f, "Snek ate itself"),
+            DeathCause::OtherSnek => write!(f, "Snek ran into another Snek"),
+            DeathCause::HeadOn => write!(f, "Snek bumped heads with another Snek"),
+        }
+    }
+}
+
+/* Where the game is at.  Once it leaves Running, step() stops doing
+ * anything.  With several Sneks the game runs until the last one dies,
+ * and Dead holds whatever killed it.  The game is Won once there's no
+ * empty cell left on the board, or when any Snek reaches the target
+ * length or points set in the rules.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GameState {
+    Running,
+    Dead(DeathCause),
+    Won,
+}
+
+/* Things that happened during a step(), in the order they happened.
+ * Frontends use these to drive sounds, effects and stats without
+ * having to diff the board between ticks.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum SnekEvent {
+    BerryEaten { snek: SnekId, pos: SnekPosition, kind: BerryKind },
+    BerrySpawned { pos: SnekPosition, kind: BerryKind },
+    /* A golden berry went uneaten for too long. */
+    BerryExpired { pos: SnekPosition },
+    RockSpawned { pos: SnekPosition },
+    RocksCleared { count: usize },
+    /* Snek gained a segment; length counts the head. */
+    Grew { snek: SnekId, length: usize },
+    Shrank { snek: SnekId, length: usize },
+    /* The game should now run at this percentage of its usual tick rate. */
+    SpeedChanged { percent: u32 },
+    Died { snek: SnekId, cause: DeathCause },
+    /* Snek filled the board or reached the rules' target. */
+    Won,
+}
+
+/* How many turns can be queued up between ticks.  Anything past this
+ * is dropped, so mashing keys can't build up a backlog of moves that
+ * play out long after the player has let go.
+ */
+pub(crate) const MAX_PENDING_TURNS: usize = 3;
+
+/* One Snek and everything that belongs to it. */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snek {
+    id: SnekId,
+    head_pos: SnekPosition,
+    head_dir: SnekDirection,
+    segments: Vec<SnekPosition>,
+    segments_pending: usize,
+    /* Turns requested since the last tick, applied one per step(). */
+    turns: VecDeque<SnekDirection>,
+    points: u64,
+    death: Option<DeathCause>,
+}
+
+impl Snek {
+    fn new(id: SnekId, head_pos: SnekPosition, head_dir: SnekDirection, length: usize) -> Snek {
+        Snek {
+            id,
+            head_pos,
+            head_dir,
+            segments: Vec::new(),
+            segments_pending: length,
+            turns: VecDeque::new(),
+            points: 0,
+            death: None,
+        }
+    }
+
+    pub fn id(&self) -> SnekId {
+        self.id
+    }
+
+    pub fn head_pos(&self) -> SnekPosition {
+        self.head_pos
+    }
+
+    pub fn head_dir(&self) -> SnekDirection {
+        self.head_dir
+    }
+
+    /* Body segments, from just behind the head to the tip of the tail. */
+    pub fn segments(&self) -> &[SnekPosition] {
+        &self.segments
+    }
+
+    /* Length, counting the head. */
+    pub fn length(&self) -> usize {
+        self.segments.len() + 1
+    }
+
+    /* Segments still to grow out behind the head. */
+    pub fn pending_growth(&self) -> usize {
+        self.segments_pending
+    }
+
+    pub fn points(&self) -> u64 {
+        self.points
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.death.is_none()
+    }
+
+    pub fn death(&self) -> Option<DeathCause> {
+        self.death
+    }
+
+    /* The direction this Snek will be facing once its queued turns
+     * have all been applied.
+     */
+    pub fn next_dir(&self) -> SnekDirection {
+        *self.turns.back().unwrap_or(&self.head_dir)
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SnekGame {
-    pub game_over: bool,
-    xsize: usize,
-    ysize: usize,
-    // FIXME - is board needed?  Maybe just have Hashmaps of objects.
-    board: HashMap<SnekPosition, SnekObject>,
-
-    snek_head_pos: SnekPosition,
-    snek_head_dir: SnekDirection,
-    snek_segments: Vec<SnekPosition>,
-    snek_segments_pending: usize,
+    state: GameState,
+    rules: GameRules,
+    /* The arena this game was loaded from, if any. */
+    map: Option<SnekMap>,
+    board: Board,
+
+    sneks: Vec<Snek>,
+
+    /* Golden berries on the board and the tick they rot away on. */
+    berry_timers: Vec<(SnekPosition, u64)>,
+    /* Ticks left before a speed berry wears off. */
+    speed_ticks: u64,
+    /* Berries eaten while the board was too full to put a new one down. */
+    berries_owed: usize,
+
+    /* Ticks and elapsed time only count while the game is running;
+     * elapsed time is read from the clock once per step.  Points are
+     * kept per Snek.
+     */
+    ticks: u64,
+    elapsed: Duration,
+    /* The clock isn't saved with the game.  A loaded game gets a fresh
+     * SystemClock, which starts from zero.
+     */
+    #[serde(skip)]
+    last_clock: Duration,
+    #[serde(skip, default = "default_clock")]
+    clock: Box<dyn Clock>,
+
+    /* All randomness in a game comes out of this, so a seed plus the
+     * same inputs always plays out the same way.  ChaCha is used
+     * rather than StdRng because its output is stable across rand
+     * releases and platforms.
+     */
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+fn default_clock() -> Box<dyn Clock> {
+    Box::new(SystemClock::new())
 }
 
 impl SnekGame {
 
     pub fn new(xsize: usize, ysize: usize) -> SnekGame {
-        let snek_head_pos = SnekPosition { x: 10, y: 10, };
-        let snek_head_dir = SnekDirection::East;
+        SnekGame::with_seed(xsize, ysize, rand::thread_rng().gen())
+    }
+
+    /* Start a game whose random events (berry and rock placement)
+     * are entirely determined by the seed.
+     */
+    pub fn with_seed(xsize: usize, ysize: usize, seed: u64) -> SnekGame {
+        SnekGame::with_rules(GameRules::new().board_size(xsize, ysize), seed)
+    }
+
+    /* Start a game of some variant of Snek.  Panics if the rules don't
+     * pass GameRules::validate().
+     */
+    pub fn with_rules(rules: GameRules, seed: u64) -> SnekGame {
+        if let Err(e) = rules.validate() {
+            panic!("invalid rules: {}", e);
+        }
+        let mut game = SnekGame::empty(rules, seed);
+        game.add_initial_objects();
+        game
+    }
+
+    /* Load an arena from a map file and start a game on it with the
+     * default rules.  Everything on the board comes from the map.
+     */
+    pub fn from_map<P: AsRef<Path>>(path: P) -> Result<SnekGame, MapError> {
+        let map = SnekMap::load(path)?;
+        Ok(SnekGame::on_map(&map, GameRules::new(), rand::thread_rng().gen()))
+    }
+
+    /* Start a game on the given arena with only what the map puts on
+     * it: the rules' initial berries and rocks are ignored.  This is
+     * how a game is normally played on a map.
+     */
+    pub fn on_map(map: &SnekMap, rules: GameRules, seed: u64) -> SnekGame {
+        SnekGame::with_map(map, rules.initial_berries(0).initial_rocks(0), seed)
+    }
+
+    /* Start a game on the given arena.  The map decides the board size
+     * and where the Sneks start; anything else comes from the rules,
+     * including any random berries and rocks to scatter around on top
+     * of the map's.  Panics, like with_rules(), if the result isn't a
+     * playable game; maps that came through SnekMap's parser always are.
+     */
+    pub fn with_map(map: &SnekMap, rules: GameRules, seed: u64) -> SnekGame {
+        let rules = map.fit_rules(rules);
+        if let Err(e) = map.validate().and_then(|_| rules.validate()) {
+            panic!("invalid rules for map: {}", e);
+        }
+        let mut game = SnekGame::empty(rules, seed);
+        for pos in &map.walls {
+            game.set_cell(pos, SnekObject::Wall);
+        }
+        for pos in &map.rocks {
+            game.set_cell(pos, SnekObject::Rock);
+        }
+        for (pos, kind) in &map.berries {
+            game.set_cell(pos, SnekObject::Berry(*kind));
+            if *kind == BerryKind::Golden {
+                game.berry_timers.push((*pos, game.rules.golden_berry_ticks));
+            }
+        }
+        game.map = Some(map.clone());
+        game.add_initial_objects();
+        game
+    }
+
+    /* Check that a game that didn't come from one of the constructors,
+     * such as one read back from a save, hangs together: its rules are
+     * playable, the board is the size they say, there's a Snek for each
+     * start, and everything is on the board.
+     */
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        self.rules.validate()?;
+        if let Some(map) = &self.map {
+            map.validate()?;
+        }
+        let (xsize, ysize) = (self.board.xsize(), self.board.ysize());
+        if (xsize, ysize) != (self.rules.xsize, self.rules.ysize) {
+            return Err(format!("board is {}x{}, but the rules say {}x{}",
+                xsize, ysize, self.rules.xsize, self.rules.ysize));
+        }
+        if self.sneks.len() != self.rules.starts.len() {
+            return Err(format!("{} Sneks for {} players", self.sneks.len(), self.rules.starts.len()));
+        }
+        let on_board = |pos: &SnekPosition| {
+            (0..xsize as i32).contains(&pos.x) && (0..ysize as i32).contains(&pos.y)
+        };
+        for (id, snek) in self.sneks.iter().enumerate() {
+            if snek.id != id {
+                return Err(format!("Snek {} is stored as Snek {}", snek.id, id));
+            }
+            if let Some(pos) = std::iter::once(&snek.head_pos).chain(&snek.segments).find(|pos| !on_board(*pos)) {
+                return Err(format!("Snek {} is at ({}, {}), off the {}x{} board",
+                    id, pos.x, pos.y, xsize, ysize));
+            }
+        }
+        if let Some((pos, _)) = self.berry_timers.iter().find(|(pos, _)| !on_board(pos)) {
+            return Err(format!("berry at ({}, {}) is off the {}x{} board", pos.x, pos.y, xsize, ysize));
+        }
+        Ok(())
+    }
+
+    /* A board with nothing but the Sneks' heads on it. */
+    fn empty(rules: GameRules, seed: u64) -> SnekGame {
+        let sneks = rules.starts.iter().enumerate()
+            .map(|(id, (pos, dir))| Snek::new(id, *pos, *dir, rules.starting_length))
+            .collect();
+        let board = Board::new(rules.xsize, rules.ysize);
         let mut game = SnekGame {
-                game_over: false,
-                xsize,
-                ysize,
-                board: HashMap::new(),
-                snek_head_pos,
-                snek_head_dir,
-                snek_segments: Vec::new(),
-                snek_segments_pending: 3,
+                state: GameState::Running,
+                rules,
+                map: None,
+                board,
+                sneks,
+                berry_timers: Vec::new(),
+                speed_ticks: 0,
+                berries_owed: 0,
+                ticks: 0,
+                elapsed: Duration::ZERO,
+                last_clock: Duration::ZERO,
+                clock: Box::new(SystemClock::new()),
+                seed,
+                rng: ChaCha8Rng::seed_from_u64(seed),
         };
-        game.set_cell(&snek_head_pos, SnekObject::Head);
+        for i in 0..game.sneks.len() {
+            let pos = game.sneks[i].head_pos;
+            game.set_cell(&pos, SnekObject::Head(i));
+        }
         // for i in 0..snek_length {
             // let seg_x = snek_head_pos.0 - 1 - i as i32;
             // game.set_cell((snek_head_pos.0 - 1 - i as i32) as usize, snek_head_pos.1 as usize, SnekObject::Segment);
             // self.snek_seg_pos.push(())
         // }
-        
-        let berry_pos = SnekPosition { x: 14, y: 10, };
-        game.set_cell(&berry_pos, SnekObject::Berry);
-        let rock_pos = SnekPosition { x: 20, y: 10, };
-        game.set_cell(&rock_pos, SnekObject::Rock);
         game
     }
 
-    /* Add a berry in a random, empty cell. */
-    fn add_berry(&mut self) {
-        self.add_random_object(SnekObject::Berry);
+    fn add_initial_objects(&mut self) {
+        for _ in 0..self.rules.initial_berries {
+            self.add_berry();
+        }
+        for _ in 0..self.rules.initial_rocks {
+            self.add_rock();
+        }
     }
 
-    /* Add a rock in a random, empty cell. */
-    fn add_rock(&mut self) {
-        self.add_random_object(SnekObject::Rock);
-    }
-
-    fn add_random_object(&mut self, obj: SnekObject) {
-        let mut rng = rand::thread_rng();
-        loop {
-            let x: i32 = rng.gen_range(0..self.xsize).try_into().unwrap();
-            let y: i32 = rng.gen_range(0..self.ysize).try_into().unwrap();
-            let berry_pos: SnekPosition = SnekPosition { x, y };
-            if self.get_cell(&berry_pos) == SnekObject::Empty {
-                self.set_cell(&berry_pos, obj);
+    /* Add a berry in a random, empty cell.  Returns None, placing
+     * nothing, if the board is full; the same goes for the other
+     * add_*() functions.
+     */
+    fn add_berry(&mut self) -> Option<SnekPosition> {
+        self.add_random_object(SnekObject::Berry(BerryKind::Normal))
+    }
+
+    /* Roll for a bonus berry, picking its kind by the rules' spawn
+     * weights.  Returns the berry placed, if any.
+     */
+    fn add_bonus_berry(&mut self) -> Option<(SnekPosition, BerryKind)> {
+        if self.rng.gen_range(0..100u32) >= self.rules.bonus_berry_chance {
+            return None;
+        }
+        let total: u32 = self.rules.bonus_berry_weights.iter().map(|(_, w)| w).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = self.rng.gen_range(0..total);
+        let mut kind = BerryKind::Normal;
+        for (k, weight) in &self.rules.bonus_berry_weights {
+            if roll < *weight {
+                kind = *k;
                 break;
             }
+            roll -= weight;
+        }
+        let pos = self.add_random_object(SnekObject::Berry(kind))?;
+        if kind == BerryKind::Golden {
+            self.berry_timers.push((pos, self.ticks + self.rules.golden_berry_ticks));
         }
+        Some((pos, kind))
+    }
+
+    /* Add a rock in a random, empty cell. */
+    fn add_rock(&mut self) -> Option<SnekPosition> {
+        self.add_random_object(SnekObject::Rock)
+    }
+
+    fn add_random_object(&mut self, obj: SnekObject) -> Option<SnekPosition> {
+        let pos = self.board.random_free(&mut self.rng)?;
+        self.set_cell(&pos, obj);
+        Some(pos)
     }
 
     /* Set a cell on the gameboard to a particular object.  If
@@ -109,18 +472,7 @@ impl SnekGame {
      * although should possibly be an error?
      */
     fn set_cell(&mut self, pos: &SnekPosition, obj: SnekObject) {
-        /* Sanity check on bounds. */
-        if pos.x >= self.xsize as i32 || pos.y >= self.ysize as i32 {
-            return;
-        }
-
-        /* If the cell was already occupied, clear it. */
-        if self.get_cell(pos) != SnekObject::Empty {
-            self.board.remove(pos);
-        }
-
-        /* Populate the cell with the specified SnekObject. */
-        self.board.insert(*pos, obj);
+        self.board.set(pos, obj);
     }
 
     /* Get the contents of a cell on the gameboard.  If the
@@ -129,289 +481,805 @@ impl SnekGame {
      * usize to make it easy to iterate over adjacent cells
      * which might land outside of the board.
      */
-    fn get_cell(&self, pos: &SnekPosition) -> SnekObject {
-        let ysize: i32 = self.ysize.try_into().unwrap();
-        let xsize: i32 = self.xsize.try_into().unwrap();
-        if pos.x < 0 || pos.x >= xsize || pos.y < 0 || pos.y > ysize {
-            return SnekObject::Wall;
+    pub fn get_cell(&self, pos: &SnekPosition) -> SnekObject {
+        self.board.get(pos).unwrap_or(SnekObject::Wall)
+    }
+
+    /* The cell one step from pos in the given direction.  On a wrapping
+     * board this never leaves the board; otherwise it may land outside,
+     * where get_cell() reports a wall.
+     */
+    pub fn neighbor(&self, pos: &SnekPosition, dir: SnekDirection) -> SnekPosition {
+        let mut new_pos = *pos;
+        match dir {
+            SnekDirection::North => {
+                new_pos.y -= 1;
+            }
+            SnekDirection::East => {
+                new_pos.x += 1;
+            }
+            SnekDirection::West => {
+                new_pos.x -= 1;
+            }
+            SnekDirection::South => {
+                new_pos.y += 1;
+            }
+        }
+        if self.rules.wrap {
+            let ysize: i32 = self.rules.ysize.try_into().unwrap();
+            let xsize: i32 = self.rules.xsize.try_into().unwrap();
+            new_pos.x = new_pos.x.rem_euclid(xsize);
+            new_pos.y = new_pos.y.rem_euclid(ysize);
         }
-        // let x: usize = x.try_into().unwrap();
-        // let y: usize = y.try_into().unwrap();
-        if let Some(cell) = self.board.get(pos) {
-            cell.clone()
+        new_pos
+    }
+
+    /* Board dimensions, in cells. */
+    pub fn xsize(&self) -> usize {
+        self.rules.xsize
+    }
+
+    pub fn ysize(&self) -> usize {
+        self.rules.ysize
+    }
+
+    pub fn rules(&self) -> &GameRules {
+        &self.rules
+    }
+
+    pub fn map(&self) -> Option<&SnekMap> {
+        self.map.as_ref()
+    }
+
+    pub fn state(&self) -> GameState {
+        self.state
+    }
+
+    /* True once the game has ended, whether Snek died or won. */
+    pub fn is_over(&self) -> bool {
+        self.state != GameState::Running
+    }
+
+    /* Swap in a different time source, e.g. a ManualClock for tests.
+     * Time already counted is kept.
+     */
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.last_clock = clock.now();
+        self.clock = clock;
+    }
+
+    /* Draw the game as text with the given theme.  Plain {} uses the
+     * default one.
+     */
+    pub fn display<'a>(&'a self, theme: &'a TextTheme) -> Themed<'a> {
+        Themed { game: self, theme }
+    }
+
+    /* Don't count the time since the last step, such as while the
+     * frontend was paused.
+     */
+    pub fn resume_clock(&mut self) {
+        self.last_clock = self.clock.now();
+    }
+
+    /* Points scored by the first Snek.  See sneks() for everyone else. */
+    pub fn points(&self) -> u64 {
+        self.sneks[0].points
+    }
+
+    /* Number of steps Snek has survived. */
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /* Wall-clock time spent playing, up to the last step. */
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /* How fast the game should currently be ticking, as a percentage
+     * of the frontend's normal rate.  Speed berries change this.
+     */
+    pub fn tick_rate_percent(&self) -> u32 {
+        if self.speed_ticks > 0 {
+            self.rules.speed_berry_percent
         } else {
-            SnekObject::Empty
+            100
         }
     }
 
-    /* Updates Snek's direction.  If you try to turn back on yourself,
-     * it becomes a no-op.
+    /* The first Snek's length, counting the head. */
+    pub fn length(&self) -> usize {
+        self.sneks[0].length()
+    }
+
+    /* The seed this game was started with. */
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /* Every Snek in the game, indexed by SnekId, dead ones included. */
+    pub fn sneks(&self) -> &[Snek] {
+        &self.sneks
+    }
+
+    pub fn snek(&self, id: SnekId) -> Option<&Snek> {
+        self.sneks.get(id)
+    }
+
+    /* Shorthands for the first Snek, which is the only one in a
+     * single-player game.
+     */
+    pub fn snek_head_pos(&self) -> SnekPosition {
+        self.sneks[0].head_pos
+    }
+
+    pub fn snek_head_dir(&self) -> SnekDirection {
+        self.sneks[0].head_dir
+    }
+
+    /* Iterate over every occupied cell on the board, row by row.
+     * Empty cells are skipped.
+     */
+    pub fn cells(&self) -> impl Iterator<Item = (SnekPosition, SnekObject)> + '_ {
+        self.board.iter()
+    }
+
+    /* How many cells are still empty. */
+    pub fn free_cells(&self) -> usize {
+        self.board.free_cells()
+    }
+
+    /* Queues a turn for Snek, to be applied on an upcoming step().  Turns
+     * are checked against the direction Snek will be facing once every
+     * turn already in the queue has been applied, so two quick presses
+     * between ticks can't add up to Snek turning back on itself.  Turning
+     * backwards, "turning" to the direction already being faced, or
+     * overflowing the queue are all no-ops.
      */
-    // FIXME: 2022/01/11 - jbradach - this doesn't quite cover everything.  If I hit a full loop
-    // FIXME: faster than a "tick" I can crash into myself in the same direction that gets drawn.
-    // FIXME: ...do I need to delay a frame?  Or make it draw one more past game over...
     pub fn set_snekdir(&mut self, dir: SnekDirection) {
-        let curdir = &self.snek_head_dir;
-        match curdir {
-            SnekDirection::North => { if dir == SnekDirection::South { return; } },
-            SnekDirection::East => { if dir == SnekDirection::West { return; } },
-            SnekDirection::West => { if dir == SnekDirection::East { return; } },
-            SnekDirection::South => { if dir == SnekDirection::North { return; } },
+        self.set_snek_dir(0, dir);
+    }
+
+    /* set_snekdir() for a particular Snek.  Unknown ids are ignored. */
+    pub fn set_snek_dir(&mut self, id: SnekId, dir: SnekDirection) {
+        let snek = match self.sneks.get_mut(id) {
+            Some(snek) => snek,
+            None => return,
+        };
+        let curdir = snek.next_dir();
+        if dir == curdir || dir == curdir.opposite() {
+            return;
         }
-        self.snek_head_dir = dir;
+        if snek.turns.len() >= MAX_PENDING_TURNS {
+            return;
+        }
+        snek.turns.push_back(dir);
     }
 
-    // Called every game step
-    pub fn step(&mut self) {
+    // Called every game step.  Returns what happened during the step.
+    pub fn step(&mut self) -> Vec<SnekEvent> {
+        let mut events = Vec::new();
+
         // Check if game is in end state.  No-op if true.
-        if self.game_over {
-            return;
+        if self.is_over() {
+            return events;
         }
 
-        // Snek moves one step in facing direction
-        let pos = self.snek_head_pos;
-        let mut new_pos = pos;
-        match self.snek_head_dir {
-            SnekDirection::North => {
-                new_pos.y -= 1;
+        let now = self.clock.now();
+        self.elapsed += now.saturating_sub(self.last_clock);
+        self.last_clock = now;
+
+        // Every living Snek takes at most one queued turn per tick and
+        // moves one step in its facing direction.
+        let mut moves = Vec::new();
+        for snek in self.sneks.iter_mut().filter(|snek| snek.is_alive()) {
+            if let Some(dir) = snek.turns.pop_front() {
+                snek.head_dir = dir;
             }
-            SnekDirection::East => {
-                new_pos.x += 1;
+        }
+        for snek in self.sneks.iter().filter(|snek| snek.is_alive()) {
+            moves.push((snek.id, self.neighbor(&snek.head_pos, snek.head_dir)));
+        }
+
+        /* Work out who dies before anybody moves, so the order Sneks are
+         * stored in doesn't matter.  Running into any part of a Snek is
+         * fatal, even a tail that's about to move out of the way.
+         */
+        let mut deaths = Vec::new();
+        for &(id, new_pos) in &moves {
+            let old_pos = self.sneks[id].head_pos;
+            let head_on = moves.iter().any(|&(other, other_new)| {
+                other != id && (other_new == new_pos
+                    || (other_new == old_pos && new_pos == self.sneks[other].head_pos))
+            });
+            let cause = if head_on {
+                Some(DeathCause::HeadOn)
+            } else {
+                match self.get_cell(&new_pos) {
+                    SnekObject::Wall => Some(DeathCause::Wall),
+                    SnekObject::Rock => Some(DeathCause::Rock),
+                    SnekObject::Head(owner) | SnekObject::Segment(owner) => {
+                        if owner == id {
+                            Some(DeathCause::SelfCollision)
+                        } else {
+                            Some(DeathCause::OtherSnek)
+                        }
+                    },
+                    _ => None,
+                }
+            };
+            if let Some(cause) = cause {
+                deaths.push((id, cause));
             }
-            SnekDirection::West => {
-                new_pos.x -= 1;
+        }
+        for &(id, cause) in &deaths {
+            self.sneks[id].death = Some(cause);
+            events.push(SnekEvent::Died { snek: id, cause });
+        }
+        if let Some(&(_, cause)) = deaths.last() {
+            if self.sneks.iter().all(|snek| !snek.is_alive()) {
+                self.state = GameState::Dead(cause);
+                return events;
             }
-            SnekDirection::South => {
-                new_pos.y += 1;
+        }
+
+        for (id, new_pos) in moves {
+            if self.sneks[id].is_alive() {
+                self.move_snek(id, new_pos, &mut events);
+            }
+        }
+
+        /* What eating normal berries brings on goes down once every Snek
+         * has moved, so nothing lands in a cell another Snek was about
+         * to move into.
+         */
+        let eaten = events.iter()
+            .filter(|event| matches!(event, SnekEvent::BerryEaten { kind: BerryKind::Normal, .. }))
+            .count();
+        for _ in 0..eaten {
+            self.spawn_after_berry(&mut events);
+        }
+        self.ticks += 1;
+
+        /* Let uneaten golden berries rot and speed berries wear off. */
+        let ticks = self.ticks;
+        let (expired, timers) = self.berry_timers.drain(..).partition(|(_, t)| *t <= ticks);
+        self.berry_timers = timers;
+        for (pos, _) in expired {
+            self.board.clear(&pos);
+            events.push(SnekEvent::BerryExpired { pos });
+        }
+        if self.speed_ticks > 0 {
+            self.speed_ticks -= 1;
+            if self.speed_ticks == 0 {
+                events.push(SnekEvent::SpeedChanged { percent: self.tick_rate_percent() });
             }
         }
 
-        match self.get_cell(&new_pos) {
-            SnekObject::Berry => {
-                self.snek_segments_pending += 1;
-                self.add_berry();
-                self.add_rock();
+        /* A berry that had nowhere to go when the last one was eaten
+         * goes down as soon as a cell frees up.
+         */
+        while self.berries_owed > 0 {
+            match self.add_berry() {
+                Some(pos) => events.push(SnekEvent::BerrySpawned { pos, kind: BerryKind::Normal }),
+                None => break,
+            }
+            self.berries_owed -= 1;
+        }
+
+        if self.has_won() {
+            self.state = GameState::Won;
+            events.push(SnekEvent::Won);
+        }
+        events
+    }
+
+    /* The board's full, or somebody has reached the rules' target. */
+    fn has_won(&self) -> bool {
+        if self.board.free_cells() == 0 {
+            return true;
+        }
+        let rules = &self.rules;
+        self.sneks.iter().filter(|snek| snek.is_alive()).any(|snek| {
+            rules.win_length.is_some_and(|length| snek.length() >= length)
+                || rules.win_points.is_some_and(|points| snek.points >= points)
+        })
+    }
+
+    /* Move a Snek that's known to survive into new_pos, eating whatever
+     * berry is there.
+     */
+    fn move_snek(&mut self, id: SnekId, new_pos: SnekPosition, events: &mut Vec<SnekEvent>) {
+        let mut shrink = 0;
+        if let SnekObject::Berry(kind) = self.get_cell(&new_pos) {
+            shrink = self.eat_berry(id, new_pos, kind, events);
+        }
+
+        /* Move the head on the board and update the head position. */
+        let pos = self.sneks[id].head_pos;
+        self.set_cell(&new_pos, SnekObject::Head(id));
+
+        let snek = &mut self.sneks[id];
+        snek.head_pos = new_pos;
+
+        /* The old head becomes the first segment.  If we have segments waiting to be
+         * appended to Snek, that's all; otherwise the tail moves up behind it. */
+        snek.segments.insert(0, pos);
+        let mut vacated = Vec::new();
+        if snek.segments_pending > 0 {
+            snek.segments_pending -= 1;
+            events.push(SnekEvent::Grew { snek: id, length: snek.length() });
+        } else {
+            vacated.extend(snek.segments.pop());
+        }
+
+        if shrink > 0 {
+            let keep = snek.segments.len().saturating_sub(shrink);
+            vacated.extend(snek.segments.drain(keep..));
+            events.push(SnekEvent::Shrank { snek: id, length: snek.length() });
+        }
+
+        let has_segments = !snek.segments.is_empty();
+        for cell in vacated {
+            self.board.clear(&cell);
+        }
+        if has_segments {
+            self.board.set(&pos, SnekObject::Segment(id));
+        }
+    }
+
+    /* Put down the berry that replaces an eaten normal one, along with
+     * its rocks and maybe a bonus berry.
+     */
+    fn spawn_after_berry(&mut self, events: &mut Vec<SnekEvent>) {
+        match self.add_berry() {
+            Some(pos) => events.push(SnekEvent::BerrySpawned { pos, kind: BerryKind::Normal }),
+            None => self.berries_owed += 1,
+        }
+        for pos in (0..self.rules.rocks_per_berry).filter_map(|_| self.add_rock()) {
+            events.push(SnekEvent::RockSpawned { pos });
+        }
+        if let Some((pos, kind)) = self.add_bonus_berry() {
+            events.push(SnekEvent::BerrySpawned { pos, kind });
+        }
+    }
+
+    /* Apply the effects of a berry.  Returns how many segments the
+     * Snek that ate it should lose.
+     */
+    fn eat_berry(&mut self, id: SnekId, pos: SnekPosition, kind: BerryKind, events: &mut Vec<SnekEvent>) -> usize {
+        events.push(SnekEvent::BerryEaten { snek: id, pos, kind });
+        self.berry_timers.retain(|(p, _)| *p != pos);
+        match kind {
+            BerryKind::Normal => {
+                self.sneks[id].points += self.rules.points_per_berry;
+                self.sneks[id].segments_pending += self.rules.growth_per_berry;
             },
-            SnekObject::Wall => {
-                self.game_over = true;
-                return;
+            BerryKind::Golden => {
+                self.sneks[id].points += self.rules.golden_berry_points;
+                self.sneks[id].segments_pending += self.rules.growth_per_berry;
             },
-            SnekObject::Rock => {
-                self.game_over = true;
-                return;
+            BerryKind::Shrink => {
+                return self.rules.shrink_berry_segments;
             },
-            SnekObject::Segment => {
-                self.game_over = true;
-                return;
+            BerryKind::Speed => {
+                self.speed_ticks = self.rules.speed_berry_ticks;
+                events.push(SnekEvent::SpeedChanged { percent: self.tick_rate_percent() });
+            },
+            BerryKind::RockClear => {
+                let count = self.board.clear_all(SnekObject::Rock);
+                events.push(SnekEvent::RocksCleared { count });
             },
-            _ => { /* WARK! */ },
         }
+        0
+    }
+}
 
-        /* Move the head on the board and update the head position. */
-        self.board.remove(&pos);
-        self.set_cell(&new_pos, SnekObject::Head);
-        self.snek_head_pos = new_pos;
-
-        /* Clear last round's segments from the board. */
-        for i in 0..self.snek_segments.len() {
-            self.board.remove(&self.snek_segments[i].clone());
-        }
-
-        /* Update the segment positions.  If we have segments waiting to be appended to Snek,
-         * insert a new one at current head (x,y).  If head element (x,y) == head (x,y), don't
-         * draw it and don't pop tail. */
-        if self.snek_segments_pending > 0 {
-            self.snek_segments.insert(0, pos);
-            self.snek_segments_pending -= 1;
-        }
-
-        if self.snek_segments[0] != pos {
-            self.snek_segments.insert(0, pos);
-            self.snek_segments.pop();
-        }
-        
-        for i in 0..self.snek_segments.len() {
-            self.set_cell(&self.snek_segments[i].clone(), SnekObject::Segment);
-        }
-    }
-
-    // Need a translate snakepos on the board to xy on the canvas.
-    fn translate_snakepos_to_pos(&self, _canvas: &Canvas<Window>) -> (usize, usize) {
-        (0, 0)
-    }
-
-    pub fn draw(&self, canvas: &mut Canvas<Window>) {
-        self.draw_board(canvas);
-        self.draw_head(canvas);
-        self.draw_segments(canvas);
-        self.draw_rocks(canvas);
-        self.draw_berries(canvas);
-        // self.draw_grid(canvas);
-    }
-
-    fn draw_board(&self, canvas: &mut Canvas<Window>) {
-        let (max_x, max_y) = canvas.output_size().unwrap();
-        let BORDER_COLOR = Color::RGB(0, 200, 0);
-        let board_max_x = 3 * (max_x / 4);
-        let board_max_y = max_y;
-        // Draw line around full canvas, 3-5 pixel thickness.  Rounded borders.
-        // Draw line at 3/4ths (2/3rds or other fraction?) vertically
-        // Left is gameboard, right is "game status"
-        // Add "Snek!" text to status
-        // Maybe animate it?
-        // It should be a TTF font, C64?
-        let (x1, y1) = (0, 0);
-        let (x2, y2) = (board_max_x, board_max_y);
-        
-        // self.draw_line(&self, (x1, y1), (x2, y2));
-        canvas.set_draw_color(BORDER_COLOR);
-        canvas.set_draw_color(
-            Color {
-                r: BORDER_COLOR.r / 2,
-                g: BORDER_COLOR.g / 2,
-                b: BORDER_COLOR.b / 2,
-                a: BORDER_COLOR.a,
-            }
-        );
-        
-
-
-
-        println!("{}, {}", board_max_x, board_max_y);
-        
-    }
-
-    fn draw_head(&self, canvas: &mut Canvas<Window>) {
-        const HEAD_COLOR: Color = Color::RGB(0, 80, 80);
-        const FACE_COLOR: Color = Color::RGB(255, 80, 80);
-        const FACE_WIDTH: u32 = 4;
-        let pos = self.snek_head_pos;
-        let x = pos.x * 32;
-        let y = pos.y * 32;
-        let orig_color = canvas.draw_color();
-        canvas.set_draw_color(HEAD_COLOR);
-        let _ = canvas.fill_rect(Rect::new(x, y, 32, 32));
-        canvas.set_draw_color(FACE_COLOR);
-        // match self.snek_head_dir {
-            // SnekDirection::North => { canvas.fill_rect(Rect::new(x, y, 32, FACE_WIDTH)); },
-            // SnekDirection::East => { canvas.fill_rect(Rect::new(x+32 - FACE_WIDTH, y, FACE_WIDTH, 32)); },
-            // SnekDirection::West => { canvas.fill_rect(Rect::new(x, y, 32, 2)); },
-            // SnekDirection::South => { canvas.fill_rect(Rect::new(x, y, 32, 1)); },
-        // }
 
-        canvas.set_draw_color(orig_color);
+impl fmt::Display for SnekGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.display(&TextTheme::default()))
     }
+}
 
-    fn draw_segments(&self, canvas: &mut Canvas<Window>) {
-        const SEGMENT_COLOR: Color = Color::RGB(255, 255, 0);
-        let mut segments = self.board.clone();
-        segments.retain(|_, v| v.clone() == SnekObject::Segment);
-        let orig_color = canvas.draw_color();
-        canvas.set_draw_color(SEGMENT_COLOR);
-        for pos in segments.iter() {
-            let x = pos.0.x * 32;
-            let y = pos.0.y * 32;
-            let _ =canvas.fill_rect(Rect::new(x, y, 32, 32));
+/* Strip the colour codes Display puts in, so coloured output pasted
+ * from a terminal or log still parses.
+ */
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            out.push(c);
         }
-        canvas.set_draw_color(orig_color);
     }
+    out
+}
+
+/* Build a game from the text Display prints, in either the default or
+ * the ASCII theme:
+ *
+ *     __________
+ *     __■■■→____
+ *     __■_____▄_
+ *     __■■__█___
+ *
+ * Players are numbered in reading order by where their heads are.
+ * Bodies are traced back from each head through neighbouring segments,
+ * starting with the cell behind it; where the text could be read more
+ * than one way, any reading that uses every segment will do, though a
+ * big enough tangle can still be given up on.  A dotted
+ * frame means the board wraps.  Everything the text doesn't show, such
+ * as points, pending growth and timers, starts from zero, and the
+ * game's RNG from seed 0, except that golden berries get their full
+ * lifetime.
+ */
+impl FromStr for SnekGame {
+    type Err = MapError;
+
+    fn from_str(s: &str) -> Result<SnekGame, MapError> {
+        let themes = [TextTheme::default(), TextTheme::ascii()];
+        let text = strip_ansi(s);
+        let mut rows: Vec<Vec<char>> = text.lines()
+            .map(|row| row.trim_end_matches('\r').chars().collect())
+            .collect();
+        while rows.last().is_some_and(|row| row.is_empty()) {
+            rows.pop();
+        }
+        if rows.is_empty() {
+            return Err(MapError::Invalid("board is empty".to_string()));
+        }
+
+        let is_edge = |c: &char| themes.iter().any(|theme| theme.wrap_edge.symbol == *c);
+        let wrap = rows.len() > 2 && rows[0].len() >= 2 && rows[0].iter().all(is_edge);
+        let (skip, rows) = if wrap {
+            // Every row needs both sides of the frame before it's peeled off.
+            let width = rows[0].len();
+            for (y, row) in rows.iter().enumerate() {
+                if row.len() != width {
+                    return Err(MapError::Syntax {
+                        line: y + 1,
+                        column: row.len().min(width) + 1,
+                        message: format!("row is {} characters wide, expected {}", row.len(), width),
+                    });
+                }
+            }
+            let inner: Vec<Vec<char>> = rows[1..rows.len() - 1].iter()
+                .map(|row| row[1..width - 1].to_vec())
+                .collect();
+            (1, inner)
+        } else {
+            (0, rows)
+        };
 
-    fn draw_berries(&self, canvas: &mut Canvas<Window>) {
-        const BERRY_COLOR: Color = Color::RGB(255, 0, 0);
-        let mut berries = self.board.clone();
-        berries.retain(|_, v| v.clone() == SnekObject::Berry);
-        let orig_color = canvas.draw_color();
-        canvas.set_draw_color(BERRY_COLOR);
-        for pos in berries.iter() {
-            let x = pos.0.x * 32;
-            let y = pos.0.y * 32;
-            let _ = canvas.fill_rect(Rect::new(x, y, 32, 32));
+        let xsize = rows[0].len();
+        let mut objects = Vec::new();
+        let mut heads = Vec::new();
+        let mut segments = HashSet::new();
+        for (y, row) in rows.iter().enumerate() {
+            if row.len() != xsize {
+                return Err(MapError::Syntax {
+                    line: y + skip + 1,
+                    column: row.len().min(xsize) + skip + 1,
+                    message: format!("row is {} cells wide, expected {}", row.len(), xsize),
+                });
+            }
+            for (x, c) in row.iter().enumerate() {
+                let pos = SnekPosition { x: x as i32, y: y as i32 };
+                match themes.iter().find_map(|theme| theme.parse(*c)) {
+                    Some(Drawn::Object(SnekObject::Empty)) => {},
+                    Some(Drawn::Object(obj)) => objects.push((pos, obj)),
+                    Some(Drawn::Head(dir)) => heads.push((pos, dir)),
+                    Some(Drawn::Segment) => { segments.insert(pos); },
+                    None => return Err(MapError::Syntax {
+                        line: y + skip + 1,
+                        column: x + skip + 1,
+                        message: format!("unknown glyph '{}'", c),
+                    }),
+                }
+            }
+        }
+        if heads.is_empty() {
+            return Err(MapError::Invalid("no Snek head on the board".to_string()));
+        }
+
+        let mut rules = GameRules::new()
+            .board_size(xsize, rows.len())
+            .wrap(wrap)
+            .initial_berries(0)
+            .initial_rocks(0);
+        rules.starts = heads.clone();
+        rules.validate().map_err(MapError::Invalid)?;
+        let mut game = SnekGame::empty(rules, 0);
+        for (pos, obj) in objects {
+            game.set_cell(&pos, obj);
+            // A golden berry rots away as if it had just turned up.
+            if obj == SnekObject::Berry(BerryKind::Golden) {
+                game.berry_timers.push((pos, game.rules.golden_berry_ticks));
+            }
         }
-        canvas.set_draw_color(orig_color);
+
+        let bodies = game.trace_bodies(&heads, segments)
+            .ok_or_else(|| MapError::Invalid("segments don't join up into Sneks".to_string()))?;
+        for (snek, body) in game.sneks.iter_mut().zip(bodies) {
+            for pos in &body {
+                game.board.set(pos, SnekObject::Segment(snek.id));
+            }
+            snek.segments = body;
+            snek.segments_pending = 0;
+        }
+        Ok(game)
     }
+}
+
+/* How many steps each attempt at tracing bodies may take, and how many
+ * attempts to make before giving up on a board.  Each attempt breaks
+ * ties between equally good cells a different way, since a tangle that
+ * one ordering gets lost in is often quick for another.  Boards printed
+ * by Display almost always trace on the first attempt.
+ */
+const TRACE_STEPS: usize = 1_000;
+const TRACE_ATTEMPTS: u64 = 20;
+
+/* How tracing from some point on turned out. */
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Traced {
+    Found,
+    DeadEnd,
+    OutOfSteps,
+}
 
-    fn draw_rocks(&self, canvas: &mut Canvas<Window>) {
-        const ROCK_COLOR: Color = Color::RGB(120, 120, 120);
-        let mut rocks = self.board.clone();
-        rocks.retain(|_, v| v.clone() == SnekObject::Rock);
-        let orig_color = canvas.draw_color();
-        canvas.set_draw_color(ROCK_COLOR);
-        for pos in rocks.iter() {
-            let x = pos.0.x * 32;
-            let y = pos.0.y * 32;
-            let _ = canvas.fill_rect(Rect::new(x, y, 32, 32));
+/* Shares segments out among Sneks, each body running from just behind
+ * its head to its tail.  Follows a body wherever only one segment
+ * carries on from its end, and only branches where more than one does.
+ */
+struct BodyTracer<'a> {
+    game: &'a SnekGame,
+    heads: &'a [(SnekPosition, SnekDirection)],
+    /* Segments not yet in a body. */
+    left: HashSet<SnekPosition>,
+    /* The bodies traced so far; the last one is still growing. */
+    bodies: Vec<Vec<SnekPosition>>,
+    /* States known to lead nowhere: which Snek is being traced, where
+     * its body has got to, and which segments are left, as a bitmap.
+     */
+    dead: HashSet<(usize, SnekPosition, Vec<u64>)>,
+    steps: usize,
+    attempt: u64,
+}
+
+impl BodyTracer<'_> {
+    fn neighbors(&self, pos: &SnekPosition) -> Vec<SnekPosition> {
+        let mut cells = Vec::with_capacity(4);
+        for dir in [SnekDirection::North, SnekDirection::East, SnekDirection::South, SnekDirection::West] {
+            let next = self.game.neighbor(pos, dir);
+            if next != *pos && !cells.contains(&next) {
+                cells.push(next);
+            }
         }
-        canvas.set_draw_color(orig_color);
+        cells
     }
 
-    /* Draw the game grid. */
-    pub fn draw_grid(&self, canvas: &mut Canvas<Window>) {
-        const SPACING: usize = 32;
-        const GRID_COLOR: Color = Color::RGB(0, 255, 0);
-        const WALL_COLOR: Color = Color::RGB(255, 0, 0);
+    /* Where the body being traced ends, and the heads of the Sneks
+     * still to come; all the segments left have to hang off these.
+     */
+    fn ends(&self) -> Vec<SnekPosition> {
+        let current = self.bodies.len() - 1;
+        let mut ends = vec![*self.bodies[current].last().unwrap_or(&self.heads[current].0)];
+        ends.extend(self.heads[current + 1..].iter().map(|(pos, _)| *pos));
+        ends
+    }
 
-        let orig_color = canvas.draw_color();
+    /* Whether the segments left can't possibly be shared out from here.
+     * Each bunch of segments that touch one another is covered by the
+     * bodies running into it from the ends next to it, and one end can
+     * only run into one bunch.  A bunch needs an end next to it, can't
+     * have more dead-end cells than there are bodies to finish in them,
+     * and, where the board can be coloured like a chessboard, can't
+     * lean further towards one colour than those bodies can, as a body
+     * alternates colours starting with the one its end isn't.
+     */
+    fn stuck(&self) -> bool {
+        let ends = self.ends();
+        let rules = &self.game.rules;
+        let chequered = !(rules.wrap && (rules.xsize % 2 == 1 || rules.ysize % 2 == 1));
+        let dark = |pos: &SnekPosition| (pos.x + pos.y).rem_euclid(2) == 1;
 
-        // Get surface dimensions, coerce into i32 so we can use them as Points.
-        let (xsize, ysize) = canvas.output_size().unwrap();
-        let xsize: i32 = xsize.try_into().unwrap();
-        let ysize: i32 = ysize.try_into().unwrap();
+        let mut seen = HashSet::new();
+        let mut claimed = Vec::new();
+        let mut bunches = 0;
+        for start in &self.left {
+            if !seen.insert(*start) {
+                continue;
+            }
+            bunches += 1;
+            let mut bunch = vec![*start];
+            let mut i = 0;
+            while i < bunch.len() {
+                for next in self.neighbors(&bunch[i]) {
+                    if self.left.contains(&next) && seen.insert(next) {
+                        bunch.push(next);
+                    }
+                }
+                i += 1;
+            }
 
-        // Draw the grid
-        canvas.set_draw_color(GRID_COLOR);
-        for x in (0..xsize).step_by(SPACING) {
-            canvas.draw_line(Point::new(x, 0), Point::new(x, ysize)).unwrap();
+            let mut touching = Vec::new();
+            let mut tails = 0;
+            for pos in &bunch {
+                let mut ways = 0;
+                for next in self.neighbors(pos) {
+                    if self.left.contains(&next) {
+                        ways += 1;
+                    } else if let Some(end) = ends.iter().position(|end| *end == next) {
+                        ways += 1;
+                        if !touching.contains(&end) {
+                            touching.push(end);
+                        }
+                    }
+                }
+                if ways <= 1 {
+                    tails += 1;
+                }
+            }
+            if touching.is_empty() || tails > touching.len() {
+                return true;
+            }
+            if chequered {
+                let lean: i64 = bunch.iter().map(|pos| if dark(pos) { 1 } else { -1 }).sum();
+                let dark_ends = touching.iter().filter(|end| dark(&ends[**end])).count() as i64;
+                let light_ends = touching.len() as i64 - dark_ends;
+                if !(-dark_ends..=light_ends).contains(&lean) {
+                    return true;
+                }
+            }
+            if let [end] = touching.as_slice() {
+                if claimed.contains(end) {
+                    return true;
+                }
+                claimed.push(*end);
+            }
         }
-        for y in (0..ysize).step_by(SPACING) {
-            canvas.draw_line(Point::new(0, y), Point::new(xsize, y)).unwrap();
+        bunches > ends.len()
+    }
+
+    /* Something to break ties with that changes from one attempt to the
+     * next.  The first attempt has no say, leaving bodies to run straight
+     * on where they can.
+     */
+    fn shuffle(&self, pos: &SnekPosition) -> u64 {
+        if self.attempt == 0 {
+            return 0;
         }
+        let mut z = (pos.x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            ^ (pos.y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+            ^ self.attempt.wrapping_mul(0x1656_67b1_9e37_79f9);
+        z ^= z >> 29;
+        z = z.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z ^ (z >> 32)
+    }
+
+    fn trace(&mut self) -> Traced {
+        if self.steps == 0 {
+            return Traced::OutOfSteps;
+        }
+        self.steps -= 1;
 
-        // Draw the walls
-        canvas.set_draw_color(WALL_COLOR);
-        canvas.draw_line(Point::new(0, 0), Point::new(xsize - 1, 0)).unwrap();
-        canvas.draw_line(Point::new(0, 0), Point::new(0, ysize - 1)).unwrap();
-        canvas.draw_line(Point::new(xsize - 1, 0), Point::new(xsize - 1, ysize - 1)).unwrap();
-        canvas.draw_line(Point::new(0, ysize - 1), Point::new(xsize - 1, ysize - 1)).unwrap();
-        
-        // Restore original color
-        canvas.set_draw_color(orig_color);
+        let current = self.bodies.len() - 1;
+        let (head, facing) = self.heads[current];
+        let end = *self.bodies[current].last().unwrap_or(&head);
+        let xsize = self.game.rules.xsize;
+        let mut bitmap = vec![0u64; (xsize * self.game.rules.ysize + 63) / 64];
+        for pos in &self.left {
+            let i = pos.y as usize * xsize + pos.x as usize;
+            bitmap[i / 64] |= 1 << (i % 64);
+        }
+        let state = (current, end, bitmap);
+        if self.dead.contains(&state) {
+            return Traced::DeadEnd;
+        }
+        let traced = self.branch(head, facing, end);
+        if traced == Traced::DeadEnd {
+            self.dead.insert(state);
+        }
+        traced
     }
-}
 
+    fn branch(&mut self, head: SnekPosition, facing: SnekDirection, end: SnekPosition) -> Traced {
+        if self.stuck() {
+            return Traced::DeadEnd;
+        }
+        let current = self.bodies.len() - 1;
 
-impl fmt::Display for SnekGame {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for y in 0..self.ysize {
-            for x in 0..self.xsize {
-                let pos: SnekPosition = SnekPosition { x: x as i32, y: y as i32, };
-                let obj = self.get_cell(&pos);
-                match obj {
-                    SnekObject::Berry => {
-                        write!(f, "{}", "▄".to_string().red().bold())?
-                    },
-                    SnekObject::Head => {
-                        let v = match self.snek_head_dir {
-                            SnekDirection::North => "↑",
-                            SnekDirection::East => "→",
-                            SnekDirection::West => "←",
-                            SnekDirection::South => "↓",
-                        };
-                        let v = v.to_string().green().bold();
-                        write!(f, "{}", v)?
-                    },
-                    SnekObject::Rock => {
-                        write!(f, "{}", "█".to_string().white().bold())?
-                    },
-                    SnekObject::Segment => {
-                        write!(f, "{}", "■".to_string().yellow().bold())?
-                    },
-                                        
-                    _ => write!(f, "_")?,
-                }
+        // The first segment is normally the cell the head just left.
+        // After that, cells with the fewest ways on are the likeliest to
+        // be stranded if they're passed by, so they go first.
+        let mut next: Vec<SnekPosition> = self.neighbors(&end).into_iter()
+            .filter(|pos| self.left.contains(pos))
+            .collect();
+        if self.bodies[current].is_empty() {
+            let behind = self.game.neighbor(&head, facing.opposite());
+            next.sort_by_key(|pos| *pos != behind);
+        } else {
+            let body = &self.bodies[current];
+            let before = if body.len() >= 2 { body[body.len() - 2] } else { head };
+            let ahead = [SnekDirection::North, SnekDirection::East, SnekDirection::South, SnekDirection::West]
+                .into_iter()
+                .find(|dir| self.game.neighbor(&before, *dir) == end)
+                .map(|dir| self.game.neighbor(&end, dir));
+            next.sort_by_key(|pos| {
+                let ways = self.neighbors(pos).iter().filter(|n| self.left.contains(n)).count();
+                (ways, self.shuffle(pos), Some(*pos) != ahead)
+            });
+        }
+        for pos in next {
+            self.left.remove(&pos);
+            self.bodies[current].push(pos);
+            let traced = self.trace();
+            if traced == Traced::Found {
+                return traced;
+            }
+            self.bodies[current].pop();
+            self.left.insert(pos);
+            if traced == Traced::OutOfSteps {
+                return traced;
+            }
+        }
+
+        // Or end this body here and move on to the next Snek.
+        if current + 1 < self.heads.len() {
+            self.bodies.push(Vec::new());
+            let traced = self.trace();
+            if traced != Traced::Found {
+                self.bodies.pop();
+            }
+            traced
+        } else if self.left.is_empty() {
+            Traced::Found
+        } else {
+            Traced::DeadEnd
+        }
+    }
+}
+
+impl SnekGame {
+    /* Share the segments out among the Sneks with the given heads, or
+     * None if there's no way to.
+     */
+    fn trace_bodies(
+        &self,
+        heads: &[(SnekPosition, SnekDirection)],
+        segments: HashSet<SnekPosition>,
+    ) -> Option<Vec<Vec<SnekPosition>>> {
+        let mut tracer = BodyTracer {
+            game: self,
+            heads,
+            left: segments,
+            bodies: Vec::new(),
+            dead: HashSet::new(),
+            steps: 0,
+            attempt: 0,
+        };
+        for attempt in 0..TRACE_ATTEMPTS {
+            tracer.attempt = attempt;
+            tracer.steps = TRACE_STEPS;
+            tracer.bodies = vec![Vec::new()];
+            match tracer.trace() {
+                Traced::Found => return Some(tracer.bodies),
+                Traced::DeadEnd => return None,
+                Traced::OutOfSteps => {},
             }
-            writeln!(f)?;
         }
-        writeln!(f)
+        None
     }
 }
 
@@ -419,14 +1287,26 @@ impl fmt::Display for SnekGame {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::time::Duration;
+    use crate::clock::ManualClock;
+    use crate::controller::{HamiltonianBot, SnekController};
+
+    /* The board every game started with before GameRules: Snek at
+     * (10, 10) facing east, a berry at (14, 10) and a rock at (20, 10).
+     */
+    fn classic_game(seed: u64) -> SnekGame {
+        let rules = GameRules::new().initial_berries(0).initial_rocks(0).bonus_berry_chance(0);
+        let mut game = SnekGame::with_rules(rules, seed);
+        game.set_cell(&SnekPosition { x: 14, y: 10 }, SnekObject::Berry(BerryKind::Normal));
+        game.set_cell(&SnekPosition { x: 20, y: 10 }, SnekObject::Rock);
+        game
+    }
    
     fn do_game_steps(game: &mut SnekGame, ticks: usize) {
         for _ in 0..ticks {
             game.step();
             println!("{:?}", game);
             println!("{}", game);
-            if game.game_over {
+            if game.is_over() {
                 return;
             }
             ::std::thread::sleep(Duration::new(0, 100_000_000)); // 1 second delay for debug
@@ -435,42 +1315,438 @@ mod tests {
     
     #[test]
     fn test_snek_hit_snek() {
-        let mut game = SnekGame::new(32, 24);
-        game.set_snekdir(SnekDirection::North);
-        do_game_steps(&mut game, 2);
-        game.set_snekdir(SnekDirection::East);
-        do_game_steps(&mut game, 2);
-        game.set_snekdir(SnekDirection::South);
-        do_game_steps(&mut game, 2);
-        game.set_snekdir(SnekDirection::West);
-        do_game_steps(&mut game, 2);
-        assert_eq!(game.game_over, true);
+        // Turning left runs Snek into the middle of its own body.
+        let mut game: SnekGame = "\
+            ______\n\
+            _■■■__\n\
+            _■←■__\n\
+            _■____\n".parse().unwrap();
+        assert_eq!(game.length(), 7);
+        game.step();
+        assert_eq!(game.state(), GameState::Dead(DeathCause::SelfCollision));
+    }
+
+    #[test]
+    fn test_parse_game() {
+        let text = "\
+            ·········\n\
+            ·■■→___▄·\n\
+            ·■__↓__█·\n\
+            ·■■_■■__·\n\
+            ·········\n";
+        let game: SnekGame = text.parse().unwrap();
+        assert!(game.rules().wrap);
+        assert_eq!((game.xsize(), game.ysize()), (7, 3));
+        let pos = |x, y| SnekPosition { x, y };
+        assert_eq!(game.sneks()[0].head_dir(), SnekDirection::East);
+        assert_eq!(game.sneks()[0].segments(), &[pos(1, 0), pos(0, 0), pos(0, 1), pos(0, 2), pos(1, 2)]);
+        assert_eq!(game.sneks()[1].segments(), &[pos(3, 2), pos(4, 2)]);
+        assert_eq!(game.get_cell(&pos(6, 1)), SnekObject::Rock);
+
+        // What's printed parses back to the same board, colours and all.
+        let printed = game.to_string();
+        assert_eq!(printed.parse::<SnekGame>().unwrap().to_string(), printed);
+        assert_eq!(game.display(&TextTheme::ascii()).to_string().parse::<SnekGame>().unwrap().to_string(), printed);
+
+        assert!(matches!("_→_\n__\n".parse::<SnekGame>(), Err(MapError::Syntax { line: 2, .. })));
+        assert!(matches!("_→x\n".parse::<SnekGame>(), Err(MapError::Syntax { column: 3, .. })));
+        assert!(matches!("_→_\n___\n■__\n".parse::<SnekGame>(), Err(MapError::Invalid(_))));
+        assert!(matches!("→\n".parse::<SnekGame>(), Err(MapError::Invalid(_))));
+        assert!(matches!("···\n·\n···\n".parse::<SnekGame>(), Err(MapError::Syntax { line: 2, .. })));
+
+        // Golden berries still rot.
+        let game: SnekGame = "→__\n__●\n".parse().unwrap();
+        assert_eq!(game.berry_timers, vec![(pos(2, 1), game.rules().golden_berry_ticks)]);
+    }
+
+    #[test]
+    fn test_parse_tangled_game() {
+        let board = |size, head: (usize, usize), glyph| {
+            let mut text = String::new();
+            for y in 0..size {
+                for x in 0..size {
+                    text.push(if (x, y) == head { glyph } else { '■' });
+                }
+                text.push('\n');
+            }
+            text
+        };
+
+        // Too many segments on the head's colour for any body to cover.
+        assert!(matches!(board(7, (1, 0), '←').parse::<SnekGame>(), Err(MapError::Invalid(_))));
+        // Could be read countless ways, any of which will do.
+        let game: SnekGame = board(12, (5, 5), '↑').parse().unwrap();
+        assert_eq!(game.length(), 144);
+    }
+
+    #[test]
+    fn test_parse_long_snek() {
+        // The Hamiltonian bot coils Snek up tightly; every board it prints
+        // on the way to filling this one has to read back the same.
+        let rules = GameRules::new()
+            .board_size(10, 8)
+            .start(SnekPosition { x: 2, y: 3 }, SnekDirection::East)
+            .rocks_per_berry(0)
+            .initial_rocks(0)
+            .bonus_berry_chance(0);
+        let mut game = SnekGame::with_rules(rules, 5);
+        let mut bot = HamiltonianBot::new();
+        let mut length = game.length();
+        while !game.is_over() && game.ticks() < 20_000 {
+            let dir = bot.next_dir(&game, 0);
+            game.set_snekdir(dir);
+            game.step();
+            if game.length() != length {
+                length = game.length();
+                let printed = game.to_string();
+                let parsed: SnekGame = printed.parse().unwrap();
+                assert_eq!(parsed.length(), length);
+                assert_eq!(parsed.to_string(), printed);
+            }
+        }
+        assert!(length > 49);
     }
 
     #[test]
     fn test_snek_hit_wall() {
-        let mut game = SnekGame::new(32, 24);
+        let mut game = classic_game(0);
         game.set_snekdir(SnekDirection::North);
         do_game_steps(&mut game, 24);
-        assert_eq!(game.game_over, true);
+        assert_eq!(game.state(), GameState::Dead(DeathCause::Wall));
 
-        let mut game = SnekGame::new(32, 24);
+        // Heading east runs into the starting rock before the wall.
+        let mut game = classic_game(0);
         game.set_snekdir(SnekDirection::East);
         do_game_steps(&mut game, 32);
-        assert_eq!(game.game_over, true);
+        assert_eq!(game.state(), GameState::Dead(DeathCause::Rock));
 
-        let mut game = SnekGame::new(32, 24);
+        let mut game = classic_game(0);
         game.set_snekdir(SnekDirection::West);
         do_game_steps(&mut game, 32);
 
-        let mut game = SnekGame::new(32, 24);
+        let mut game = classic_game(0);
         game.set_snekdir(SnekDirection::South);
         do_game_steps(&mut game, 24);
     }
 
+    #[test]
+    fn test_seeded_games_match() {
+        // Head east through the starting berry so a berry and a rock get
+        // placed at random, then keep going until Snek hits something.
+        let mut a = classic_game(1234);
+        let mut b = classic_game(1234);
+        assert_eq!(a.seed(), 1234);
+        for _ in 0..32 {
+            a.step();
+            b.step();
+            assert_eq!(format!("{}", a), format!("{}", b));
+        }
+        assert_eq!(a.state(), GameState::Dead(DeathCause::Rock));
+    }
+
+    #[test]
+    fn test_turns_queue_between_ticks() {
+        // Two presses before a tick both get applied, one per step.
+        let mut game = classic_game(0);
+        game.set_snekdir(SnekDirection::North);
+        game.set_snekdir(SnekDirection::West);
+        game.step();
+        assert_eq!(game.snek_head_dir(), SnekDirection::North);
+        assert_eq!(game.snek_head_pos(), SnekPosition { x: 10, y: 9 });
+        game.step();
+        assert_eq!(game.snek_head_dir(), SnekDirection::West);
+        assert_eq!(game.snek_head_pos(), SnekPosition { x: 9, y: 9 });
+    }
+
+    #[test]
+    fn test_turns_cant_reverse_through_queue() {
+        // Facing east: North is fine, but South is checked against North,
+        // not East, so it gets dropped instead of folding Snek into its neck.
+        let mut game = classic_game(0);
+        game.set_snekdir(SnekDirection::North);
+        game.set_snekdir(SnekDirection::South);
+        game.step();
+        game.step();
+        assert_eq!(game.snek_head_dir(), SnekDirection::North);
+        assert_eq!(game.snek_head_pos(), SnekPosition { x: 10, y: 8 });
+        assert_eq!(game.state(), GameState::Running);
+
+        // West is straight back from East with nothing queued.
+        let mut game = classic_game(0);
+        game.set_snekdir(SnekDirection::West);
+        game.step();
+        assert_eq!(game.snek_head_dir(), SnekDirection::East);
+    }
+
+    #[test]
+    fn test_turn_queue_is_bounded() {
+        let mut game = classic_game(0);
+        for _ in 0..4 {
+            game.set_snekdir(SnekDirection::North);
+            game.set_snekdir(SnekDirection::East);
+        }
+        assert_eq!(game.sneks[0].turns.len(), MAX_PENDING_TURNS);
+    }
+
+    #[test]
+    fn test_step_events() {
+        let mut game = classic_game(7);
+
+        // Snek starts with three segments owed to it.
+        assert_eq!(game.step(), vec![SnekEvent::Grew { snek: 0, length: 2 }]);
+        game.step();
+        game.step();
+
+        // Fourth step lands on the starting berry at (14, 10).
+        let events = game.step();
+        assert_eq!(events[0], SnekEvent::BerryEaten {
+            snek: 0,
+            pos: SnekPosition { x: 14, y: 10 },
+            kind: BerryKind::Normal,
+        });
+        assert_eq!(events[1], SnekEvent::Grew { snek: 0, length: 5 });
+        assert!(matches!(events[2], SnekEvent::BerrySpawned { .. }));
+        assert!(matches!(events[3], SnekEvent::RockSpawned { .. }));
+
+        game.set_snekdir(SnekDirection::North);
+        let events = (0..24).flat_map(|_| game.step()).collect::<Vec<_>>();
+        assert_eq!(events.last(), Some(&SnekEvent::Died { snek: 0, cause: DeathCause::Wall }));
+        assert_eq!(game.step(), vec![]);
+    }
+
+    #[test]
+    fn test_score_tracking() {
+        let clock = ManualClock::new();
+        let mut game = classic_game(7);
+        game.set_clock(Box::new(clock.clone()));
+        for _ in 0..4 {
+            clock.advance(Duration::from_millis(100));
+            game.step();
+        }
+        assert_eq!(game.points(), game.rules().points_per_berry);
+        assert_eq!(game.ticks(), 4);
+        assert_eq!(game.length(), 5);
+        assert_eq!(game.elapsed(), Duration::from_millis(400));
+
+        // The fatal step still counts the time leading up to it, but
+        // neither ticks nor time accumulate once Snek is dead.
+        game.set_snekdir(SnekDirection::North);
+        while !game.is_over() {
+            clock.advance(Duration::from_millis(100));
+            game.step();
+        }
+        let ticks = game.ticks();
+        let elapsed = game.elapsed();
+        assert_eq!(elapsed, Duration::from_millis(100 * (ticks + 1)));
+        clock.advance(Duration::from_secs(5));
+        game.step();
+        assert_eq!(game.ticks(), ticks);
+        assert_eq!(game.elapsed(), elapsed);
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules = GameRules::new()
+            .board_size(16, 12)
+            .starting_length(1)
+            .growth_per_berry(3)
+            .rocks_per_berry(2)
+            .initial_berries(0)
+            .initial_rocks(4)
+            .start(SnekPosition { x: 2, y: 2 }, SnekDirection::South)
+            .bonus_berry_chance(0);
+        let mut game = SnekGame::with_rules(rules, 5);
+        let count = |game: &SnekGame, kind: SnekObject| {
+            game.cells().filter(|(_, obj)| *obj == kind).count()
+        };
+        assert_eq!(count(&game, SnekObject::Berry(BerryKind::Normal)), 0);
+        assert_eq!(count(&game, SnekObject::Rock), 4);
+        assert_eq!(game.snek_head_dir(), SnekDirection::South);
+
+        game.set_cell(&SnekPosition { x: 2, y: 4 }, SnekObject::Berry(BerryKind::Normal));
+        game.step();
+        let events = game.step();
+        assert_eq!(events.iter().filter(|e| matches!(e, SnekEvent::RockSpawned { .. })).count(), 2);
+        assert_eq!(count(&game, SnekObject::Rock), 6);
+        for _ in 0..3 {
+            game.step();
+        }
+        assert_eq!(game.length(), 5);
+    }
+
+    #[test]
+    fn test_wrap_around() {
+        let rules = GameRules::new()
+            .board_size(8, 6)
+            .initial_berries(0)
+            .initial_rocks(0)
+            .start(SnekPosition { x: 6, y: 1 }, SnekDirection::East)
+            .wrap(true);
+        let mut game = SnekGame::with_rules(rules, 3);
+        game.step();
+        game.step();
+        assert_eq!(game.snek_head_pos(), SnekPosition { x: 0, y: 1 });
+        game.set_snekdir(SnekDirection::North);
+        game.step();
+        game.step();
+        assert_eq!(game.snek_head_pos(), SnekPosition { x: 0, y: 5 });
+        assert_eq!(game.state(), GameState::Running);
+
+        // The off-board cell is still a wall on a regular board.
+        let rules = game.rules().clone().wrap(false);
+        let mut game = SnekGame::with_rules(rules, 3);
+        game.step();
+        game.step();
+        assert_eq!(game.state(), GameState::Dead(DeathCause::Wall));
+    }
+
+    #[test]
+    fn test_bonus_berries() {
+        let rules = GameRules::new()
+            .initial_berries(0)
+            .initial_rocks(3)
+            .shrink_berry_segments(2)
+            .bonus_berry_chance(0);
+        let mut game = SnekGame::with_rules(rules, 11);
+        let east = |n: i32| SnekPosition { x: 10 + n, y: 10 };
+        game.set_cell(&east(1), SnekObject::Berry(BerryKind::Speed));
+        game.set_cell(&east(5), SnekObject::Berry(BerryKind::Shrink));
+        game.set_cell(&east(6), SnekObject::Berry(BerryKind::RockClear));
+        game.set_cell(&SnekPosition { x: 0, y: 0 }, SnekObject::Berry(BerryKind::Golden));
+        game.berry_timers.push((SnekPosition { x: 0, y: 0 }, 8));
+
+        assert!(game.step().contains(&SnekEvent::SpeedChanged {
+            percent: game.rules().speed_berry_percent
+        }));
+        assert_ne!(game.tick_rate_percent(), 100);
+        for _ in 0..3 {
+            game.step();
+        }
+        assert_eq!(game.length(), 4);
+        assert!(game.step().contains(&SnekEvent::Shrank { snek: 0, length: 2 }));
+        assert_eq!(game.length(), 2);
+        assert!(game.step().contains(&SnekEvent::RocksCleared { count: 3 }));
+
+        // The golden berry rots away on tick 8.
+        game.step();
+        assert!(game.step().contains(&SnekEvent::BerryExpired { pos: SnekPosition { x: 0, y: 0 } }));
+        assert_eq!(game.get_cell(&SnekPosition { x: 0, y: 0 }), SnekObject::Empty);
+    }
+
+    #[test]
+    fn test_map_walls() {
+        let map: SnekMap = "#######\n#↓____#\n#__#__#\n#▄_#__#\n#######\n".parse().unwrap();
+        let mut game = SnekGame::on_map(&map, GameRules::new(), 1);
+        assert_eq!((game.xsize(), game.ysize()), (7, 5));
+        // Only the map's berry, none of the rules' random ones.
+        let scattered = (0..7).flat_map(|x| (0..5).map(move |y| SnekPosition { x, y }))
+            .filter(|pos| matches!(game.get_cell(pos), SnekObject::Berry(_) | SnekObject::Rock))
+            .count();
+        assert_eq!(scattered, 1);
+        assert_eq!(game.get_cell(&SnekPosition { x: 3, y: 2 }), SnekObject::Wall);
+        game.step();
+        game.set_snekdir(SnekDirection::East);
+        game.step();
+        game.step();
+        assert_eq!(game.state(), GameState::Dead(DeathCause::Wall));
+    }
+
+    #[test]
+    fn test_win() {
+        let mut game = classic_game(0);
+        game.rules.win_points = Some(10);
+        let events = (0..4).flat_map(|_| game.step()).collect::<Vec<_>>();
+        assert_eq!(events.last(), Some(&SnekEvent::Won));
+        assert_eq!(game.state(), GameState::Won);
+        assert_eq!(game.step(), vec![]);
+
+        // Filling the board wins too, even with nowhere left for a berry.
+        let rules = GameRules::new()
+            .board_size(3, 1)
+            .start(SnekPosition { x: 0, y: 0 }, SnekDirection::East)
+            .starting_length(0)
+            .initial_rocks(0);
+        let mut game = SnekGame::with_rules(rules, 0);
+        game.step();
+        game.step();
+        assert_eq!(game.state(), GameState::Won);
+        assert_eq!(game.free_cells(), 0);
+    }
+
+    #[test]
+    fn test_multiplayer() {
+        let two_sneks = |a: (i32, i32, SnekDirection), b: (i32, i32, SnekDirection)| {
+            let rules = GameRules::new()
+                .board_size(8, 5)
+                .initial_berries(0)
+                .initial_rocks(0)
+                .start(SnekPosition { x: a.0, y: a.1 }, a.2)
+                .add_snek(SnekPosition { x: b.0, y: b.1 }, b.2);
+            SnekGame::with_rules(rules, 0)
+        };
+
+        // Heads meeting in the same cell take out both Sneks.
+        let mut game = two_sneks((1, 2, SnekDirection::East), (5, 2, SnekDirection::West));
+        game.step();
+        let events = game.step();
+        assert!(events.contains(&SnekEvent::Died { snek: 0, cause: DeathCause::HeadOn }));
+        assert!(events.contains(&SnekEvent::Died { snek: 1, cause: DeathCause::HeadOn }));
+        assert_eq!(game.state(), GameState::Dead(DeathCause::HeadOn));
+
+        // So does trying to slip past each other.
+        let mut game = two_sneks((1, 2, SnekDirection::East), (4, 2, SnekDirection::West));
+        game.step();
+        assert!(game.step().contains(&SnekEvent::Died { snek: 1, cause: DeathCause::HeadOn }));
+
+        // Running into another Snek only kills the one doing the running,
+        // and the game carries on until the last one dies.
+        let mut game = two_sneks((0, 1, SnekDirection::East), (3, 3, SnekDirection::North));
+        game.step();
+        game.step();
+        assert_eq!(game.snek(1).unwrap().head_pos(), SnekPosition { x: 3, y: 1 });
+        assert_eq!(game.step()[0], SnekEvent::Died { snek: 0, cause: DeathCause::OtherSnek });
+        assert_eq!(game.state(), GameState::Running);
+        assert!(game.snek(1).unwrap().is_alive());
+        let events = (0..3).flat_map(|_| game.step()).collect::<Vec<_>>();
+        assert_eq!(events.last(), Some(&SnekEvent::Died { snek: 1, cause: DeathCause::Wall }));
+        assert_eq!(game.state(), GameState::Dead(DeathCause::Wall));
+    }
+
+    #[test]
+    fn test_multiplayer_spawns() {
+        // Snek 0 eats while Snek 1 moves into one of the only two empty
+        // cells.  What the berry brings on mustn't land in its way.
+        let pos = |x, y| SnekPosition { x, y };
+        let rules = GameRules::new()
+            .board_size(4, 2)
+            .starting_length(0)
+            .initial_berries(0)
+            .initial_rocks(0)
+            .bonus_berry_chance(0)
+            .start(pos(0, 0), SnekDirection::East)
+            .add_snek(pos(0, 1), SnekDirection::East);
+        for seed in 0..20 {
+            let mut game = SnekGame::with_rules(rules.clone(), seed);
+            game.set_cell(&pos(1, 0), SnekObject::Berry(BerryKind::Normal));
+            for rock in [pos(2, 0), pos(3, 0), pos(3, 1)] {
+                game.set_cell(&rock, SnekObject::Rock);
+            }
+            let events = game.step();
+            assert!(game.snek(1).unwrap().is_alive());
+            assert_eq!(game.get_cell(&pos(1, 1)), SnekObject::Head(1));
+            for event in events {
+                match event {
+                    SnekEvent::BerrySpawned { pos, kind } => assert_eq!(game.get_cell(&pos), SnekObject::Berry(kind)),
+                    SnekEvent::RockSpawned { pos } => assert_eq!(game.get_cell(&pos), SnekObject::Rock),
+                    _ => {},
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_snek_diagonal() {
-        let mut game = SnekGame::new(32, 24);
+        let mut game = classic_game(0);
         game.set_snekdir(SnekDirection::North);
         do_game_steps(&mut game, 1);
         game.set_snekdir(SnekDirection::East);
@@ -479,7 +1755,7 @@ mod tests {
         do_game_steps(&mut game, 2);
         game.set_snekdir(SnekDirection::West);
         do_game_steps(&mut game, 32);
-        assert_eq!(game.game_over, true);
+        assert_eq!(game.state(), GameState::Dead(DeathCause::Wall));
 
     }
 
@@ -0,0 +1,185 @@
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use crate::input::{Action, KeyBindings};
+use crate::render::{status_lines, Renderer};
+use crate::snek::SnekGame;
+use crate::theme::TextTheme;
+
+/* The name SDL would give key, so that one set of bindings works for
+ * both frontends.  Letters come out in upper case.
+ */
+pub fn key_name(key: KeyEvent) -> Option<String> {
+    let name = match key.code {
+        KeyCode::Up => "Up",
+        KeyCode::Right => "Right",
+        KeyCode::Down => "Down",
+        KeyCode::Left => "Left",
+        KeyCode::Esc => "Escape",
+        KeyCode::Enter => "Return",
+        KeyCode::Tab => "Tab",
+        KeyCode::Backspace => "Backspace",
+        KeyCode::Char(' ') => "Space",
+        KeyCode::Char(c) => return Some(c.to_ascii_uppercase().to_string()),
+        KeyCode::F(n) => return Some(format!("F{}", n)),
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/* What key is bound to.  Raw mode swallows Ctrl-C, so it always quits,
+ * whatever else is bound.
+ */
+pub fn key_to_action(bindings: &KeyBindings, key: KeyEvent) -> Option<Action> {
+    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        return Some(Action::Quit);
+    }
+    bindings.action(&key_name(key)?)
+}
+
+/* Wait up to timeout for a key press.  Returns None if nothing
+ * arrived, or if what did isn't bound to anything.
+ */
+pub fn poll_input(bindings: &KeyBindings, timeout: Duration) -> Result<Option<Action>, String> {
+    if !event::poll(timeout).map_err(|e| e.to_string())? {
+        return Ok(None);
+    }
+    match event::read().map_err(|e| e.to_string())? {
+        Event::Key(key) => Ok(key_to_action(bindings, key)),
+        _ => Ok(None),
+    }
+}
+
+/* Puts the terminal into raw mode on a blank alternate screen, and puts
+ * it back the way it was when dropped, even if the game panics.
+ */
+pub struct TermSession {
+    out: Stdout,
+}
+
+impl TermSession {
+    pub fn start() -> Result<TermSession, String> {
+        let mut out = io::stdout();
+        terminal::enable_raw_mode().map_err(|e| e.to_string())?;
+        execute!(out, EnterAlternateScreen, Hide).map_err(|e| e.to_string())?;
+        Ok(TermSession { out })
+    }
+}
+
+impl Drop for TermSession {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/* Draws the game as text, redrawing in place from the top left of the
+ * terminal rather than scrolling.  Below the board go the scores and a
+ * line saying which keys do what.
+ */
+pub struct TermRenderer<W: Write> {
+    out: W,
+    theme: TextTheme,
+    keys: KeyBindings,
+    paused: bool,
+    message: Option<String>,
+}
+
+impl<W: Write> TermRenderer<W> {
+    pub fn new(out: W) -> TermRenderer<W> {
+        TermRenderer { out, theme: TextTheme::default(), keys: KeyBindings::default(), paused: false, message: None }
+    }
+
+    pub fn theme(mut self, theme: TextTheme) -> TermRenderer<W> {
+        self.theme = theme;
+        self
+    }
+
+    /* The bindings to describe in the help line. */
+    pub fn keys(mut self, keys: KeyBindings) -> TermRenderer<W> {
+        self.keys = keys;
+        self
+    }
+
+    /* Say so under the board while the game is paused. */
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /* A line to show above the help line, such as where a quicksave
+     * went, until it's replaced or cleared.
+     */
+    pub fn set_message(&mut self, message: Option<String>) {
+        self.message = message;
+    }
+
+    fn help_line(&self, game: &SnekGame) -> String {
+        let first = |names: &[String]| names.first().map_or("?".to_string(), |name| name.to_lowercase());
+        let (pause, quit) = (first(&self.keys.pause), first(&self.keys.quit));
+        if game.is_over() {
+            format!("{} plays again, {} quits", first(&self.keys.restart), quit)
+        } else if self.paused {
+            format!("Paused.  {} resumes, {} quits", pause, quit)
+        } else {
+            format!("{} pauses, {} quits", pause, quit)
+        }
+    }
+}
+
+impl<W: Write> Renderer for TermRenderer<W> {
+    fn draw(&mut self, game: &SnekGame) -> Result<(), String> {
+        let board = game.display(&self.theme).to_string();
+        let mut lines: Vec<String> = board.lines().map(str::to_string).collect();
+        lines.extend(status_lines(game));
+        lines.extend(self.message.clone());
+        lines.push(self.help_line(game));
+
+        // Raw mode doesn't turn \n into \r\n, so every line is placed
+        // explicitly, and whatever the last frame left beyond it wiped.
+        for (row, line) in lines.iter().enumerate() {
+            queue!(self.out, MoveTo(0, row as u16), Print(line), Clear(ClearType::UntilNewLine))
+                .map_err(|e| e.to_string())?;
+        }
+        queue!(self.out, Clear(ClearType::FromCursorDown)).map_err(|e| e.to_string())?;
+        self.out.flush().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::GameRules;
+    use crate::snek::SnekDirection;
+
+    #[test]
+    fn test_keys() {
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let keys = KeyBindings::default();
+        assert_eq!(key_name(key(KeyCode::Char(' '))), Some("Space".to_string()));
+        assert_eq!(key_name(key(KeyCode::F(5))), Some("F5".to_string()));
+        assert_eq!(key_to_action(&keys, key(KeyCode::Left)), Some(Action::Turn(0, SnekDirection::West)));
+        assert_eq!(key_to_action(&keys, key(KeyCode::Char('S'))), Some(Action::Turn(1, SnekDirection::South)));
+        assert_eq!(key_to_action(&keys, key(KeyCode::Char('p'))), Some(Action::Pause));
+        assert_eq!(key_to_action(&keys, KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)), Some(Action::Quit));
+        assert_eq!(key_to_action(&keys, key(KeyCode::Char('x'))), None);
+    }
+
+    #[test]
+    fn test_draw() {
+        let game = SnekGame::with_rules(GameRules::new().board_size(16, 12), 1);
+        let mut renderer = TermRenderer::new(Vec::new());
+        renderer.set_paused(true);
+        renderer.set_message(Some("Saved to quicksave.json".to_string()));
+        renderer.draw(&game).unwrap();
+        let frame = String::from_utf8(renderer.out).unwrap();
+        assert!(!frame.contains('\n'));
+        assert!(frame.contains("Points: 0  Length: "));
+        assert!(frame.contains("Saved to quicksave.json"));
+        assert!(frame.contains("Paused."));
+    }
+}
@@ -0,0 +1,290 @@
+use std::fmt;
+use colored::{Color, Colorize};
+
+use crate::snek::{BerryKind, SnekDirection, SnekGame, SnekObject, SnekPosition};
+
+/* One character of text output, and how to style it when colour is on. */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glyph {
+    pub symbol: char,
+    pub color: Option<Color>,
+    pub bold: bool,
+    pub dimmed: bool,
+}
+
+impl Glyph {
+    pub const fn plain(symbol: char) -> Glyph {
+        Glyph { symbol, color: None, bold: false, dimmed: false }
+    }
+
+    pub const fn colored(symbol: char, color: Color) -> Glyph {
+        Glyph { symbol, color: Some(color), bold: false, dimmed: false }
+    }
+
+    pub const fn bold(mut self) -> Glyph {
+        self.bold = true;
+        self
+    }
+
+    pub const fn dimmed(mut self) -> Glyph {
+        self.dimmed = true;
+        self
+    }
+
+    fn write(&self, f: &mut fmt::Formatter, color: bool) -> fmt::Result {
+        if !color {
+            return write!(f, "{}", self.symbol);
+        }
+        let mut s = self.symbol.to_string().normal();
+        if let Some(c) = self.color {
+            s = s.color(c);
+        }
+        if self.bold {
+            s = s.bold();
+        }
+        if self.dimmed {
+            s = s.dimmed();
+        }
+        write!(f, "{}", s)
+    }
+}
+
+/* The glyphs and colours used to draw a game as text.  The default is
+ * what SnekGame's Display impl has always drawn; ascii() sticks to
+ * plain ASCII with no colour, for logs and CI output.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextTheme {
+    pub empty: Glyph,
+    pub wall: Glyph,
+    pub rock: Glyph,
+    pub normal_berry: Glyph,
+    pub golden_berry: Glyph,
+    pub shrink_berry: Glyph,
+    pub speed_berry: Glyph,
+    pub rock_clear_berry: Glyph,
+    /* Heads facing north, east, south and west. */
+    pub heads: [char; 4],
+    pub segment: char,
+    /* Head and body colours for each player's Snek, reused in turn if
+     * there are more players than colours.
+     */
+    pub snek_colors: Vec<(Color, Color)>,
+    /* On a wrapping board there are no walls, so this frame is drawn
+     * around it to show that the edges lead somewhere.
+     */
+    pub wrap_edge: Glyph,
+    /* Style glyphs with their colours, or leave them plain. */
+    pub color: bool,
+    /* Frame a board that doesn't wrap with wall glyphs. */
+    pub border: bool,
+}
+
+impl Default for TextTheme {
+    fn default() -> TextTheme {
+        TextTheme {
+            empty: Glyph::plain('_'),
+            wall: Glyph::colored('#', Color::White),
+            rock: Glyph::colored('█', Color::White).bold(),
+            normal_berry: Glyph::colored('▄', Color::Red).bold(),
+            golden_berry: Glyph::colored('●', Color::Yellow).bold(),
+            shrink_berry: Glyph::colored('○', Color::Blue).bold(),
+            speed_berry: Glyph::colored('»', Color::Cyan).bold(),
+            rock_clear_berry: Glyph::colored('◇', Color::Magenta).bold(),
+            heads: ['↑', '→', '↓', '←'],
+            segment: '■',
+            snek_colors: vec![
+                (Color::Green, Color::Yellow),
+                (Color::Cyan, Color::Blue),
+                (Color::Magenta, Color::Red),
+                (Color::White, Color::BrightBlack),
+            ],
+            wrap_edge: Glyph::plain('·').dimmed(),
+            color: true,
+            border: false,
+        }
+    }
+}
+
+impl TextTheme {
+    pub fn new() -> TextTheme {
+        TextTheme::default()
+    }
+
+    /* Plain ASCII, no colour.  Heads use the same ^ > v < that map
+     * files accept.
+     */
+    pub fn ascii() -> TextTheme {
+        TextTheme {
+            empty: Glyph::plain('.'),
+            wall: Glyph::plain('#'),
+            rock: Glyph::plain('@'),
+            normal_berry: Glyph::plain('*'),
+            golden_berry: Glyph::plain('$'),
+            shrink_berry: Glyph::plain('-'),
+            speed_berry: Glyph::plain('+'),
+            rock_clear_berry: Glyph::plain('%'),
+            heads: ['^', '>', 'v', '<'],
+            segment: 'o',
+            wrap_edge: Glyph::plain(':'),
+            color: false,
+            ..TextTheme::default()
+        }
+    }
+
+    pub fn color(mut self, color: bool) -> TextTheme {
+        self.color = color;
+        self
+    }
+
+    pub fn border(mut self, border: bool) -> TextTheme {
+        self.border = border;
+        self
+    }
+
+    pub fn berry(&self, kind: BerryKind) -> Glyph {
+        match kind {
+            BerryKind::Normal => self.normal_berry,
+            BerryKind::Golden => self.golden_berry,
+            BerryKind::Shrink => self.shrink_berry,
+            BerryKind::Speed => self.speed_berry,
+            BerryKind::RockClear => self.rock_clear_berry,
+        }
+    }
+
+    pub fn head(&self, dir: SnekDirection) -> char {
+        match dir {
+            SnekDirection::North => self.heads[0],
+            SnekDirection::East => self.heads[1],
+            SnekDirection::South => self.heads[2],
+            SnekDirection::West => self.heads[3],
+        }
+    }
+
+    /* How obj is drawn in game.  Heads need the game to say which way
+     * they face.
+     */
+    pub fn glyph(&self, game: &SnekGame, obj: SnekObject) -> Glyph {
+        let colors = |id: usize| self.snek_colors.get(id % self.snek_colors.len().max(1)).copied();
+        match obj {
+            SnekObject::Empty => self.empty,
+            SnekObject::Wall => self.wall,
+            SnekObject::Rock => self.rock,
+            SnekObject::Berry(kind) => self.berry(kind),
+            SnekObject::Head(id) => {
+                let dir = game.snek(id).map_or(SnekDirection::East, |snek| snek.head_dir());
+                Glyph { color: colors(id).map(|(head, _)| head), ..Glyph::plain(self.head(dir)).bold() }
+            },
+            SnekObject::Segment(id) => {
+                Glyph { color: colors(id).map(|(_, body)| body), ..Glyph::plain(self.segment).bold() }
+            },
+        }
+    }
+}
+
+/* What a character stands for when reading a game back from text.
+ * Heads and segments don't say which Snek they belong to.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Drawn {
+    Object(SnekObject),
+    Head(SnekDirection),
+    Segment,
+}
+
+impl TextTheme {
+    /* The inverse of glyph(), for characters this theme draws. */
+    pub(crate) fn parse(&self, c: char) -> Option<Drawn> {
+        let objects = [
+            (self.empty, SnekObject::Empty),
+            (self.wall, SnekObject::Wall),
+            (self.rock, SnekObject::Rock),
+            (self.normal_berry, SnekObject::Berry(BerryKind::Normal)),
+            (self.golden_berry, SnekObject::Berry(BerryKind::Golden)),
+            (self.shrink_berry, SnekObject::Berry(BerryKind::Shrink)),
+            (self.speed_berry, SnekObject::Berry(BerryKind::Speed)),
+            (self.rock_clear_berry, SnekObject::Berry(BerryKind::RockClear)),
+        ];
+        const DIRS: [SnekDirection; 4] = [
+            SnekDirection::North,
+            SnekDirection::East,
+            SnekDirection::South,
+            SnekDirection::West,
+        ];
+        if let Some((_, obj)) = objects.iter().find(|(glyph, _)| glyph.symbol == c) {
+            Some(Drawn::Object(*obj))
+        } else if let Some(i) = self.heads.iter().position(|h| *h == c) {
+            Some(Drawn::Head(DIRS[i]))
+        } else if c == self.segment {
+            Some(Drawn::Segment)
+        } else {
+            None
+        }
+    }
+}
+
+/* A game drawn with a particular theme; see SnekGame::display(). */
+pub struct Themed<'a> {
+    pub(crate) game: &'a SnekGame,
+    pub(crate) theme: &'a TextTheme,
+}
+
+impl fmt::Display for Themed<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (game, theme) = (self.game, self.theme);
+        let frame = match (game.rules().wrap, theme.border) {
+            (true, _) => Some(theme.wrap_edge),
+            (false, true) => Some(theme.wall),
+            (false, false) => None,
+        };
+        let frame_row = |f: &mut fmt::Formatter| -> fmt::Result {
+            if let Some(glyph) = frame {
+                for _ in 0..game.xsize() + 2 {
+                    glyph.write(f, theme.color)?;
+                }
+                writeln!(f)?;
+            }
+            Ok(())
+        };
+
+        frame_row(f)?;
+        for y in 0..game.ysize() {
+            if let Some(glyph) = frame {
+                glyph.write(f, theme.color)?;
+            }
+            for x in 0..game.xsize() {
+                let pos = SnekPosition { x: x as i32, y: y as i32 };
+                theme.glyph(game, game.get_cell(&pos)).write(f, theme.color)?;
+            }
+            if let Some(glyph) = frame {
+                glyph.write(f, theme.color)?;
+            }
+            writeln!(f)?;
+        }
+        frame_row(f)?;
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::SnekMap;
+    use crate::rules::GameRules;
+
+    #[test]
+    fn test_ascii_theme() {
+        let map: SnekMap = "______\n_→__▄_\n_____█\n".parse().unwrap();
+        let rules = GameRules::new().initial_berries(0).initial_rocks(0);
+        let game = SnekGame::with_map(&map, rules, 0);
+
+        let theme = TextTheme::ascii();
+        assert_eq!(game.display(&theme).to_string(), "......\n.>..*.\n.....@\n\n");
+        assert_eq!(
+            game.display(&theme.border(true)).to_string(),
+            "########\n#......#\n#.>..*.#\n#.....@#\n########\n\n"
+        );
+        let plain = game.display(&TextTheme::default().color(false)).to_string();
+        assert_eq!(plain, "______\n_→__▄_\n_____█\n\n");
+    }
+}